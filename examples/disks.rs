@@ -5,36 +5,26 @@
 use anyhow::Result;
 
 fn main() -> Result<()> {
-    let mut di = devinfo::DevInfo::new()?;
+    let di = devinfo::DevInfo::new()?;
+    let links = devinfo::DevLinks::new(false)?;
 
-    let mut w = di.walk_node();
-    while let Some(n) = w.next().transpose()? {
-        let mut wm = n.minors();
-        while let Some(m) = wm.next().transpose()? {
-            /*
-             * Disks will either have the DDI_NT_BLOCK node type, or one of the
-             * more specific DDI_NT_BLOCK* subtypes (with a suffix after the
-             * colon):
-             */
-            if m.node_type() != "ddi_block"
-                && !m.node_type().starts_with("ddi_block:")
-            {
-                continue;
-            }
-
-            /*
-             * Just look for raw (not block) disk devices.
-             */
-            if m.spec_type() != devinfo::SpecType::Char {
-                continue;
-            }
+    /*
+     * Disks will either have the DDI_NT_BLOCK node type, or one of the more
+     * specific DDI_NT_BLOCK* subtypes (with a suffix after the colon):
+     */
+    let mut w = di.walk_minors(Some(devinfo::nodetype::DDI_NT_BLOCK));
+    while let Some((_, m)) = w.next().transpose()? {
+        /*
+         * Just look for raw (not block) disk devices.
+         */
+        if m.spec_type() != devinfo::SpecType::Char {
+            continue;
+        }
 
-            println!("{}: {}", m.node_type(), m.devfs_path()?);
+        println!("{}: {}", m.node_type(), m.devfs_path()?);
 
-            let links = devinfo::DevLinks::new(false)?;
-            for l in links.links_for_path(m.devfs_path()?)? {
-                println!("    {:?}", l.path());
-            }
+        for l in links.links_for_path(m.devfs_path()?)? {
+            println!("    {:?}", l.path());
         }
     }
 
@@ -5,12 +5,13 @@
 use anyhow::Result;
 
 fn main() -> Result<()> {
-    let mut di = devinfo::DevInfo::new()?;
+    let di = devinfo::DevInfo::new()?;
 
-    let mut w = di.walk_node();
-    while let Some(n) = w.next().transpose()? {
+    let mut w = di.walk_entries();
+    while let Some(e) = w.next().transpose()? {
+        let n = e.node;
         let mut ind = "".to_string();
-        for _ in 1..n.depth() {
+        for _ in 1..e.depth {
             ind.push_str("    ");
         }
 
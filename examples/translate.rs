@@ -8,10 +8,8 @@ fn main() -> Result<()> {
     let dim = devinfo::DevInstMinor::new()?;
 
     for driver in ["blkdev", "sd"] {
-        for instance in 0u32..64 {
-            if let Some(nam) = dim.lookup_disk_name(driver, instance) {
-                println!("{driver}{instance} -> {nam}");
-            }
+        for (instance, nam) in dim.lookup_all(driver)? {
+            println!("{driver}{instance} -> {nam}");
         }
     }
 
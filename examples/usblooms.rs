@@ -37,20 +37,20 @@ impl UsbDevice {
 
         let mut pw = n.props();
         while let Some(p) = pw.next().transpose().ok()? {
-            match p.name().as_str() {
-                "usb-vendor-id" => {
+            match p.name_bytes() {
+                b"usb-vendor-id" => {
                     usbvend = Some(p);
                 }
-                "usb-product-id" => {
+                b"usb-product-id" => {
                     usbprod = Some(p);
                 }
-                "usb-vendor-name" => {
+                b"usb-vendor-name" => {
                     usbvendname = Some(p);
                 }
-                "usb-product-name" => {
+                b"usb-product-name" => {
                     usbprodname = Some(p);
                 }
-                "usb-serialno" => {
+                b"usb-serialno" => {
                     usbser = Some(p);
                 }
                 _ => {}
@@ -279,7 +279,7 @@ fn main() -> Result<()> {
         Default::default();
     let mut spares: Vec<UsbDevice> = Default::default();
 
-    let mut di = devinfo::DevInfo::new()?;
+    let di = devinfo::DevInfo::new()?;
 
     let mut w = di.walk_node();
     'outer: while let Some(n) = w.next().transpose()? {
@@ -300,7 +300,7 @@ fn main() -> Result<()> {
             for devdef in loomdef.devices.iter() {
                 if devdef.vendor != ud.vendor
                     || devdef.product != ud.product
-                    || &devdef.serial != &ud.serialno
+                    || devdef.serial != ud.serialno
                 {
                     continue;
                 }
@@ -0,0 +1,108 @@
+/*
+ * Copyright 2024 Oxide Computer Company
+ */
+
+/*
+ * Cross-check the devlink database against the /dev tree in the file system
+ * and against a device tree snapshot, to find links that are stale or
+ * missing.
+ */
+
+use crate::{devfs_path_full, DEVFS_ROOT};
+use crate::{DevInfo, DevLink, DevLinkType, DevLinks, InvalidDevLink};
+use anyhow::Result;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsStr;
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AuditReport {
+    /**
+     * Links whose target does not exist; e.g., because the device has been
+     * removed.
+     */
+    pub dangling: Vec<DevLink>,
+    /**
+     * Links in the database that are not present in /dev.
+     */
+    pub missing: Vec<DevLink>,
+    /**
+     * Minor nodes (by /devices path) with more than one primary link.
+     */
+    pub duplicate_primary: BTreeMap<PathBuf, Vec<DevLink>>,
+    /**
+     * Minor nodes (by /devices path) in the snapshot that have no links at
+     * all.  Many minor nodes legitimately have no links, so consumers will
+     * generally want to filter this list; e.g., by node type.
+     */
+    pub unlinked_minors: Vec<PathBuf>,
+    /**
+     * Entries in the database that could not be decoded.
+     */
+    pub invalid: Vec<InvalidDevLink>,
+}
+
+impl AuditReport {
+    /**
+     * Report whether any problems were found, ignoring unlinked minors.
+     */
+    pub fn is_clean(&self) -> bool {
+        self.dangling.is_empty()
+            && self.missing.is_empty()
+            && self.duplicate_primary.is_empty()
+            && self.invalid.is_empty()
+    }
+}
+
+/**
+ * Audit every link in the devlink database, and every minor node in the
+ * snapshot.  The snapshot should include minor nodes.
+ */
+pub fn audit(di: &DevInfo, links: &DevLinks) -> Result<AuditReport> {
+    let mut report = AuditReport::default();
+    let mut primary: BTreeMap<PathBuf, Vec<DevLink>> = BTreeMap::new();
+    let mut linked: BTreeSet<PathBuf> = BTreeSet::new();
+    let mut err = None;
+
+    report.invalid = links.walk(None, |l| {
+        if !l.exists() {
+            report.missing.push(l.clone());
+        }
+
+        match l.is_dangling() {
+            Ok(true) => report.dangling.push(l.clone()),
+            Ok(false) => (),
+            Err(e) => {
+                err = Some(e);
+                return ControlFlow::Break(());
+            }
+        }
+
+        let target = l.canonical_target();
+        if target.starts_with(DEVFS_ROOT) {
+            linked.insert(target.clone());
+            if l.linktype() == DevLinkType::Primary {
+                primary.entry(target).or_default().push(l);
+            }
+        }
+
+        ControlFlow::Continue(())
+    })?;
+    if let Some(e) = err {
+        return Err(e);
+    }
+
+    report.duplicate_primary =
+        primary.into_iter().filter(|(_, links)| links.len() > 1).collect();
+
+    for res in di.walk_minors(None) {
+        let (_, m) = res?;
+        let path = devfs_path_full(OsStr::new(&m.devfs_path()?));
+        if !linked.contains(&path) {
+            report.unlinked_minors.push(path);
+        }
+    }
+
+    Ok(report)
+}
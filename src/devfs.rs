@@ -0,0 +1,257 @@
+/*
+ * Copyright 2024 Oxide Computer Company
+ */
+
+use anyhow::{bail, Result};
+use std::str::FromStr;
+
+/**
+ * A single component of a devfs path; e.g., "pci1022,1483@1,2" has the node
+ * name "pci1022,1483" and the unit address "1,2".
+ */
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DevfsPathComponent {
+    name: String,
+    address: Option<String>,
+}
+
+impl DevfsPathComponent {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn unit_address(&self) -> Option<&str> {
+        self.address.as_deref()
+    }
+}
+
+impl std::fmt::Display for DevfsPathComponent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(address) = &self.address {
+            write!(f, "{}@{}", self.name, address)
+        } else {
+            write!(f, "{}", self.name)
+        }
+    }
+}
+
+impl FromStr for DevfsPathComponent {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, address) = match s.split_once('@') {
+            Some((name, "")) => (name, None),
+            Some((name, address)) => (name, Some(address.to_string())),
+            None => (s, None),
+        };
+
+        if name.is_empty() {
+            bail!("devfs path component {:?} has no node name", s);
+        }
+
+        Ok(DevfsPathComponent { name: name.to_string(), address })
+    }
+}
+
+/**
+ * A parsed devfs path, as returned by di_devfs_path(3DEVINFO) or
+ * di_devfs_minor_path(3DEVINFO); e.g.,
+ * "/pci@0,0/pci1022,1483@1,2/pci1b96,0@0/blkdev@w0014EE8401D04A00,0:a".
+ *
+ * When parsing, a leading "/devices" prefix and any empty components are
+ * discarded so that equivalent paths compare as equal.
+ */
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DevfsPath {
+    components: Vec<DevfsPathComponent>,
+    minor: Option<String>,
+}
+
+impl DevfsPath {
+    /**
+     * The root of the device tree; i.e., "/".
+     */
+    pub fn root() -> Self {
+        DevfsPath { components: Vec::new(), minor: None }
+    }
+
+    pub fn components(&self) -> &[DevfsPathComponent] {
+        &self.components
+    }
+
+    /**
+     * The final component of the path, or None for the root node.
+     */
+    pub fn last(&self) -> Option<&DevfsPathComponent> {
+        self.components.last()
+    }
+
+    /**
+     * The minor name, if this is the path of a minor node; e.g., "a,raw".
+     */
+    pub fn minor(&self) -> Option<&str> {
+        self.minor.as_deref()
+    }
+
+    /**
+     * The path of the node itself, without any minor name.
+     */
+    pub fn node_path(&self) -> DevfsPath {
+        DevfsPath { components: self.components.clone(), minor: None }
+    }
+
+    /**
+     * The path of the parent node, or None for the root node.
+     */
+    pub fn parent(&self) -> Option<DevfsPath> {
+        let (_, rest) = self.components.split_last()?;
+        Some(DevfsPath { components: rest.to_vec(), minor: None })
+    }
+
+    /**
+     * Returns true if the node this path refers to is the same as, or is
+     * below, the node referred to by "other".  Minor names are ignored.
+     */
+    pub fn starts_with(&self, other: &DevfsPath) -> bool {
+        self.components.starts_with(&other.components)
+    }
+
+    pub fn with_minor(&self, minor: &str) -> DevfsPath {
+        DevfsPath {
+            components: self.components.clone(),
+            minor: Some(minor.to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for DevfsPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.components.is_empty() {
+            write!(f, "/")?;
+        }
+        for c in self.components.iter() {
+            write!(f, "/{}", c)?;
+        }
+        if let Some(minor) = &self.minor {
+            write!(f, ":{}", minor)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for DevfsPath {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let Some(rest) = s.strip_prefix('/') else {
+            bail!("devfs path {:?} is not absolute", s);
+        };
+
+        /*
+         * Accept both "/devices/pci@0,0" and "/pci@0,0" forms.
+         */
+        let rest = if rest == "devices" {
+            ""
+        } else {
+            rest.strip_prefix("devices/").unwrap_or(rest)
+        };
+
+        /*
+         * The minor name, if present, follows a colon in the final
+         * component.
+         */
+        let start = rest.rfind('/').map(|i| i + 1).unwrap_or(0);
+        let (rest, minor) = match rest[start..].find(':') {
+            Some(i) => (&rest[..start + i], Some(&rest[start + i + 1..])),
+            None => (rest, None),
+        };
+
+        let components = rest
+            .split('/')
+            .filter(|c| !c.is_empty())
+            .map(DevfsPathComponent::from_str)
+            .collect::<Result<Vec<_>>>()?;
+
+        if let Some(minor) = minor {
+            if components.is_empty() {
+                bail!("devfs path {:?} has a minor name but no node", s);
+            }
+            if minor.is_empty() {
+                bail!("devfs path {:?} has an empty minor name", s);
+            }
+        }
+
+        Ok(DevfsPath { components, minor: minor.map(str::to_string) })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for s in [
+            "/",
+            "/pci@0,0",
+            "/pci@0,0/pci1022,1483@1,2/pci1b96,0@0/blkdev@w0014EE8401D04A00,0",
+            "/pci@0,0/pci1022,1483@1,2/pci1b96,0@0/blkdev@w0014EE8401D04A00,0:a",
+            "/pseudo/zconsnex@1/zcons@0:masterconsole",
+            "/options",
+        ] {
+            let p: DevfsPath = s.parse().unwrap();
+            assert_eq!(p.to_string(), s);
+        }
+    }
+
+    #[test]
+    fn components() {
+        let p: DevfsPath =
+            "/pci@0,0/pci1022,1483@1,2/disk@0:a,raw".parse().unwrap();
+        let names: Vec<_> = p.components().iter().map(|c| c.name()).collect();
+        assert_eq!(names, ["pci", "pci1022,1483", "disk"]);
+        assert_eq!(p.last().unwrap().unit_address(), Some("0"));
+        assert_eq!(p.minor(), Some("a,raw"));
+        assert_eq!(
+            p.node_path().to_string(),
+            "/pci@0,0/pci1022,1483@1,2/disk@0"
+        );
+        assert_eq!(
+            p.parent().unwrap().to_string(),
+            "/pci@0,0/pci1022,1483@1,2"
+        );
+        assert!(p.starts_with(&"/pci@0,0".parse().unwrap()));
+        assert!(!p.starts_with(&"/pci@1,0".parse().unwrap()));
+        assert_eq!(DevfsPath::root().parent(), None);
+    }
+
+    #[test]
+    fn normalise() {
+        let a: DevfsPath = "/devices/pci@0,0//disk@0:a".parse().unwrap();
+        let b: DevfsPath = "/pci@0,0/disk@0:a".parse().unwrap();
+        assert_eq!(a, b);
+        assert_eq!("/devices".parse::<DevfsPath>().unwrap(), DevfsPath::root());
+
+        /*
+         * An empty unit address is the same as none at all.
+         */
+        let c: DevfsPathComponent = "pseudo@".parse().unwrap();
+        assert_eq!(c.unit_address(), None);
+        assert_eq!(c.to_string(), "pseudo");
+
+        /*
+         * A colon in a component other than the last is part of that
+         * component, not a minor name.
+         */
+        let d: DevfsPath = "/a@1:x/b@2".parse().unwrap();
+        assert_eq!(d.minor(), None);
+        assert_eq!(d.components()[0].unit_address(), Some("1:x"));
+    }
+
+    #[test]
+    fn malformed() {
+        for s in ["", "pci@0,0", "/:a", "/pci@0,0:", "/@0", "/pci@0,0/@1"] {
+            assert!(s.parse::<DevfsPath>().is_err(), "{:?} parsed", s);
+        }
+    }
+}
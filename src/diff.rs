@@ -0,0 +1,397 @@
+/*
+ * Copyright 2024 Oxide Computer Company
+ */
+
+/*
+ * Comparison of two device tree snapshots.  Nodes are matched between the
+ * snapshots by devfs path, minors by name within each node, and properties by
+ * name and associated dev_t within each node.  Sibling nodes that have not
+ * been assigned a unit address share a devfs path; such nodes are matched in
+ * the order in which they appear in each snapshot.
+ */
+
+use crate::{
+    DevInfo, OwnedDevTree, OwnedMinor, OwnedNode, OwnedProperty, PropDevt,
+};
+use anyhow::Result;
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    /**
+     * A node is present only in the new snapshot.  Its minors and properties
+     * are not reported separately.
+     */
+    NodeAdded {
+        devfs_path: String,
+    },
+    /**
+     * A node is present only in the old snapshot.  Its minors and properties
+     * are not reported separately.
+     */
+    NodeRemoved {
+        devfs_path: String,
+    },
+    DriverChanged {
+        devfs_path: String,
+        old: Option<String>,
+        new: Option<String>,
+    },
+    InstanceChanged {
+        devfs_path: String,
+        old: Option<i32>,
+        new: Option<i32>,
+    },
+    MinorAdded {
+        devfs_path: String,
+        minor: OwnedMinor,
+    },
+    MinorRemoved {
+        devfs_path: String,
+        minor: OwnedMinor,
+    },
+    MinorChanged {
+        devfs_path: String,
+        old: OwnedMinor,
+        new: OwnedMinor,
+    },
+    PropAdded {
+        devfs_path: String,
+        prop: OwnedProperty,
+    },
+    PropRemoved {
+        devfs_path: String,
+        prop: OwnedProperty,
+    },
+    PropChanged {
+        devfs_path: String,
+        old: OwnedProperty,
+        new: OwnedProperty,
+    },
+}
+
+impl Change {
+    /**
+     * The devfs path of the node to which this change applies.
+     */
+    pub fn devfs_path(&self) -> &str {
+        match self {
+            Change::NodeAdded { devfs_path }
+            | Change::NodeRemoved { devfs_path }
+            | Change::DriverChanged { devfs_path, .. }
+            | Change::InstanceChanged { devfs_path, .. }
+            | Change::MinorAdded { devfs_path, .. }
+            | Change::MinorRemoved { devfs_path, .. }
+            | Change::MinorChanged { devfs_path, .. }
+            | Change::PropAdded { devfs_path, .. }
+            | Change::PropRemoved { devfs_path, .. }
+            | Change::PropChanged { devfs_path, .. } => devfs_path,
+        }
+    }
+}
+
+/**
+ * The differences between two snapshots, ordered by devfs path.
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SnapshotDiff {
+    pub changes: Vec<Change>,
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn added_nodes(&self) -> impl Iterator<Item = &str> {
+        self.changes.iter().filter_map(|c| match c {
+            Change::NodeAdded { devfs_path } => Some(devfs_path.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn removed_nodes(&self) -> impl Iterator<Item = &str> {
+        self.changes.iter().filter_map(|c| match c {
+            Change::NodeRemoved { devfs_path } => Some(devfs_path.as_str()),
+            _ => None,
+        })
+    }
+}
+
+/*
+ * Key each node by its devfs path and its position among the nodes with that
+ * path, so that nodes which share a path are all included.
+ */
+fn index_nodes(tree: &OwnedDevTree) -> BTreeMap<(&str, usize), &OwnedNode> {
+    let mut seen: BTreeMap<&str, usize> = BTreeMap::new();
+    tree.walk()
+        .map(|n| {
+            let path = n.devfs_path.as_str();
+            let count = seen.entry(path).or_default();
+            *count += 1;
+            ((path, *count - 1), n)
+        })
+        .collect()
+}
+
+/*
+ * If the same property appears more than once for a given dev_t, only the
+ * first is considered, as it would be for a lookup by name.
+ */
+fn index_props(n: &OwnedNode) -> BTreeMap<(&str, PropDevt), &OwnedProperty> {
+    let mut out = BTreeMap::new();
+    for p in n.props.iter() {
+        out.entry((p.name.as_str(), p.devt)).or_insert(p);
+    }
+    out
+}
+
+fn diff_node(old: &OwnedNode, new: &OwnedNode, out: &mut Vec<Change>) {
+    let devfs_path = &new.devfs_path;
+
+    if old.driver_name != new.driver_name {
+        out.push(Change::DriverChanged {
+            devfs_path: devfs_path.clone(),
+            old: old.driver_name.clone(),
+            new: new.driver_name.clone(),
+        });
+    }
+    if old.instance != new.instance {
+        out.push(Change::InstanceChanged {
+            devfs_path: devfs_path.clone(),
+            old: old.instance,
+            new: new.instance,
+        });
+    }
+
+    let om: BTreeMap<&str, &OwnedMinor> =
+        old.minors.iter().map(|m| (m.name.as_str(), m)).collect();
+    let nm: BTreeMap<&str, &OwnedMinor> =
+        new.minors.iter().map(|m| (m.name.as_str(), m)).collect();
+    for name in om.keys().chain(nm.keys()).collect::<BTreeSet<_>>() {
+        match (om.get(name), nm.get(name)) {
+            (Some(o), None) => out.push(Change::MinorRemoved {
+                devfs_path: devfs_path.clone(),
+                minor: (*o).clone(),
+            }),
+            (None, Some(n)) => out.push(Change::MinorAdded {
+                devfs_path: devfs_path.clone(),
+                minor: (*n).clone(),
+            }),
+            (Some(o), Some(n)) if o != n => out.push(Change::MinorChanged {
+                devfs_path: devfs_path.clone(),
+                old: (*o).clone(),
+                new: (*n).clone(),
+            }),
+            _ => (),
+        }
+    }
+
+    let op = index_props(old);
+    let np = index_props(new);
+    for key in op.keys().chain(np.keys()).collect::<BTreeSet<_>>() {
+        match (op.get(key), np.get(key)) {
+            (Some(o), None) => out.push(Change::PropRemoved {
+                devfs_path: devfs_path.clone(),
+                prop: (*o).clone(),
+            }),
+            (None, Some(n)) => out.push(Change::PropAdded {
+                devfs_path: devfs_path.clone(),
+                prop: (*n).clone(),
+            }),
+            (Some(o), Some(n)) if o.value != n.value => {
+                out.push(Change::PropChanged {
+                    devfs_path: devfs_path.clone(),
+                    old: (*o).clone(),
+                    new: (*n).clone(),
+                })
+            }
+            _ => (),
+        }
+    }
+}
+
+/**
+ * Compare two owned snapshots, reporting the changes required to get from
+ * "old" to "new".
+ */
+pub fn diff(old: &OwnedDevTree, new: &OwnedDevTree) -> SnapshotDiff {
+    let on = index_nodes(old);
+    let nn = index_nodes(new);
+
+    let mut changes = Vec::new();
+    for key @ (path, _) in on.keys().chain(nn.keys()).collect::<BTreeSet<_>>() {
+        match (on.get(key), nn.get(key)) {
+            (Some(_), None) => changes
+                .push(Change::NodeRemoved { devfs_path: path.to_string() }),
+            (None, Some(_)) => {
+                changes.push(Change::NodeAdded { devfs_path: path.to_string() })
+            }
+            (Some(o), Some(n)) => diff_node(o, n, &mut changes),
+            (None, None) => unreachable!(),
+        }
+    }
+
+    SnapshotDiff { changes }
+}
+
+/**
+ * Compare two live snapshots, reporting the changes required to get from
+ * "old" to "new".
+ */
+pub fn diff_live(old: &DevInfo, new: &DevInfo) -> Result<SnapshotDiff> {
+    Ok(diff(&old.to_owned_tree()?, &new.to_owned_tree()?))
+}
+
+impl OwnedDevTree {
+    /**
+     * Compare this snapshot with a newer one.
+     */
+    pub fn diff(&self, new: &OwnedDevTree) -> SnapshotDiff {
+        diff(self, new)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{PropValue, SpecType};
+
+    fn node(path: &str, children: Vec<OwnedNode>) -> OwnedNode {
+        OwnedNode {
+            node_name: path.rsplit('/').next().unwrap().to_string(),
+            driver_name: None,
+            instance: None,
+            unit_address: None,
+            devfs_path: path.to_string(),
+            props: Vec::new(),
+            minors: Vec::new(),
+            children,
+        }
+    }
+
+    fn minor(name: &str, devt: libc::dev_t) -> OwnedMinor {
+        OwnedMinor {
+            name: name.to_string(),
+            node_type: "ddi_block".to_string(),
+            spec_type: Some(SpecType::Block),
+            devt,
+            devfs_path: format!("/pci@0/disk@1:{}", name),
+        }
+    }
+
+    fn prop(name: &str, v: i32) -> OwnedProperty {
+        OwnedProperty {
+            name: name.to_string(),
+            devt: PropDevt::None,
+            value: PropValue::Ints(vec![v]),
+        }
+    }
+
+    fn tree(children: Vec<OwnedNode>) -> OwnedDevTree {
+        OwnedDevTree { root: node("/", children) }
+    }
+
+    #[test]
+    fn changes() {
+        let old_disk = OwnedNode {
+            driver_name: Some("sd".into()),
+            instance: Some(0),
+            props: vec![prop("a", 1), prop("b", 2), prop("c", 3)],
+            minors: vec![minor("a", 1), minor("b", 2), minor("c", 3)],
+            ..node("/pci@0/disk@1", Vec::new())
+        };
+        let new_disk = OwnedNode {
+            driver_name: Some("blkdev".into()),
+            instance: Some(1),
+            props: vec![prop("a", 1), prop("b", 20), prop("d", 4)],
+            minors: vec![minor("a", 1), minor("b", 20), minor("d", 4)],
+            ..node("/pci@0/disk@1", Vec::new())
+        };
+
+        let old = tree(vec![
+            node("/pci@0", vec![old_disk]),
+            node("/pci@1", Vec::new()),
+        ]);
+        let new = tree(vec![
+            node("/pci@0", vec![new_disk]),
+            node("/pci@2", Vec::new()),
+        ]);
+
+        let d = diff(&old, &new);
+        let p = "/pci@0/disk@1".to_string();
+        assert_eq!(
+            d.changes,
+            [
+                Change::DriverChanged {
+                    devfs_path: p.clone(),
+                    old: Some("sd".into()),
+                    new: Some("blkdev".into()),
+                },
+                Change::InstanceChanged {
+                    devfs_path: p.clone(),
+                    old: Some(0),
+                    new: Some(1),
+                },
+                Change::MinorChanged {
+                    devfs_path: p.clone(),
+                    old: minor("b", 2),
+                    new: minor("b", 20),
+                },
+                Change::MinorRemoved {
+                    devfs_path: p.clone(),
+                    minor: minor("c", 3)
+                },
+                Change::MinorAdded {
+                    devfs_path: p.clone(),
+                    minor: minor("d", 4)
+                },
+                Change::PropChanged {
+                    devfs_path: p.clone(),
+                    old: prop("b", 2),
+                    new: prop("b", 20),
+                },
+                Change::PropRemoved {
+                    devfs_path: p.clone(),
+                    prop: prop("c", 3)
+                },
+                Change::PropAdded { devfs_path: p.clone(), prop: prop("d", 4) },
+                Change::NodeRemoved { devfs_path: "/pci@1".into() },
+                Change::NodeAdded { devfs_path: "/pci@2".into() },
+            ]
+        );
+        assert_eq!(d.added_nodes().collect::<Vec<_>>(), ["/pci@2"]);
+        assert_eq!(d.removed_nodes().collect::<Vec<_>>(), ["/pci@1"]);
+        assert!(diff(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn shared_paths() {
+        /*
+         * Two sibling nodes without a unit address have the same path, and
+         * neither may be lost from the comparison.
+         */
+        let old = tree(vec![node("/iscsi", Vec::new())]);
+        let new =
+            tree(vec![node("/iscsi", Vec::new()), node("/iscsi", Vec::new())]);
+        assert_eq!(
+            diff(&old, &new).changes,
+            [Change::NodeAdded { devfs_path: "/iscsi".into() }]
+        );
+        assert_eq!(
+            diff(&new, &old).changes,
+            [Change::NodeRemoved { devfs_path: "/iscsi".into() }]
+        );
+
+        let mut changed = new.clone();
+        changed.root.children[1].instance = Some(3);
+        assert_eq!(
+            diff(&new, &changed).changes,
+            [Change::InstanceChanged {
+                devfs_path: "/iscsi".into(),
+                old: None,
+                new: Some(3),
+            }]
+        );
+    }
+}
@@ -2,12 +2,13 @@
  * Copyright 2024 Oxide Computer Company
  */
 
+use crate::sys::*;
+use crate::{DevInfo, DevLinkType, DevLinks};
 use anyhow::{bail, Result};
 use libc::{c_void, free};
-use libdevinfo_sys::*;
-use std::ffi::OsStr;
+use std::collections::BTreeMap;
 use std::ffi::{CStr, CString};
-use std::os::unix::ffi::OsStrExt;
+use std::os::raw::{c_char, c_int};
 
 pub struct DevInstMinor {
     handle: *mut di_dim_t,
@@ -15,6 +16,7 @@ pub struct DevInstMinor {
 
 impl DevInstMinor {
     pub fn new() -> Result<Self> {
+        check_platform()?;
         let handle = unsafe { di_dim_init() };
         if handle.is_null() {
             let e = std::io::Error::last_os_error();
@@ -37,17 +39,36 @@ impl DevInstMinor {
         instance: u32,
         minor: &str,
     ) -> Option<String> {
+        self.lookup_path(driver, instance, minor, |h, d, i, m| unsafe {
+            di_dim_path_dev(h, d, i, m)
+        })
+    }
+
+    /*
+     * Look up a path with one of the di_dim_path_*() functions, which return
+     * an allocated string that we must free.  The instance number is an int
+     * in the C interface, so larger instances cannot exist.
+     */
+    fn lookup_path<F>(
+        &self,
+        driver: &str,
+        instance: u32,
+        minor: &str,
+        func: F,
+    ) -> Option<String>
+    where
+        F: FnOnce(
+            *mut di_dim_t,
+            *const c_char,
+            c_int,
+            *const c_char,
+        ) -> *mut c_char,
+    {
         let cdrv = CString::new(driver).ok()?;
         let cmin = CString::new(minor).ok()?;
+        let instance = c_int::try_from(instance).ok()?;
 
-        let res = unsafe {
-            di_dim_path_dev(
-                self.handle,
-                cdrv.as_ptr(),
-                instance.try_into().unwrap(),
-                cmin.as_ptr(),
-            )
-        };
+        let res = func(self.handle, cdrv.as_ptr(), instance, cmin.as_ptr());
         if res.is_null() {
             None
         } else {
@@ -58,6 +79,42 @@ impl DevInstMinor {
         }
     }
 
+    /**
+     * As with lookup_dev(), but return the path of the character (raw) device;
+     * e.g., "/dev/rdsk/c1t0025385C9150D623d0".  The raw minor name is formed
+     * by appending ",raw" to the minor name, if it is not already present.
+     */
+    pub fn lookup_dev_raw(
+        &self,
+        driver: &str,
+        instance: u32,
+        minor: &str,
+    ) -> Option<String> {
+        if minor.ends_with(",raw") {
+            self.lookup_dev(driver, instance, minor)
+        } else {
+            self.lookup_dev(driver, instance, &format!("{},raw", minor))
+        }
+    }
+
+    /**
+     * Given a driver (e.g., "blkdev"), an instance (e.g., 0), and a minor
+     * (e.g., "wd"), look up the physical /devices path for the device; e.g.,
+     * "/devices/pci@0,0/.../blkdev@w0025385C9150D623,0:wd".
+     * Unlike the /dev path, this does not depend on the state of the devlink
+     * database and may be opened directly.
+     */
+    pub fn lookup_devices(
+        &self,
+        driver: &str,
+        instance: u32,
+        minor: &str,
+    ) -> Option<String> {
+        self.lookup_path(driver, instance, minor, |h, d, i, m| unsafe {
+            di_dim_path_devices(h, d, i, m)
+        })
+    }
+
     /**
      * Given a driver (e.g., "blkdev"), and an instance (e.g., 0) look up the
      * public name for a disk device; e.g., "c1t0025385C9150D623d0".  This is
@@ -69,48 +126,84 @@ impl DevInstMinor {
         driver: &str,
         instance: u32,
     ) -> Option<String> {
-        let cdrv = CString::new(driver).ok()?;
         for minor in ["wd", "a"] {
-            let cmin = CString::new(minor).ok()?;
-
-            let res = unsafe {
-                di_dim_path_dev(
-                    self.handle,
-                    cdrv.as_ptr(),
-                    instance.try_into().unwrap(),
-                    cmin.as_ptr(),
-                )
+            let Some(path) = self.lookup_dev(driver, instance, minor) else {
+                continue;
             };
-            if res.is_null() {
+            let Some(nam) = path.strip_prefix("/dev/dsk/") else {
+                continue;
+            };
+
+            /*
+             * If we used the "a" minor, strip the slice ("s0") from the end:
+             */
+            return Some(nam.strip_suffix("s0").unwrap_or(nam).to_string());
+        }
+
+        None
+    }
+
+    /**
+     * Look up the public disk name for every instance of a driver (e.g.,
+     * "blkdev"), returning a map from instance number to name.  Rather than
+     * a lookup through this handle for each instance, the map is built from
+     * one snapshot of the driver and one walk of the /dev/dsk links in the
+     * devlink database, matched by the minor node to which each link
+     * refers.  As with lookup_disk_name(), the "wd" minor is used if the
+     * disk has one, and otherwise the "a" minor.  Instances without a disk
+     * name are omitted.
+     */
+    pub fn lookup_all(&self, driver: &str) -> Result<BTreeMap<u32, String>> {
+        let links = DevLinks::new(false)?;
+        let mut names = BTreeMap::new();
+        for l in links.links_in_dir("dsk")? {
+            if l.linktype() != DevLinkType::Primary {
                 continue;
             }
+            if let Some(nam) = l.path().file_name().and_then(|n| n.to_str()) {
+                names.insert(l.canonical_target(), nam.to_string());
+            }
+        }
 
-            let restr = unsafe { CStr::from_ptr(res) };
-            let Some(nam) = OsStr::from_bytes(restr.to_bytes())
-                .to_str()
-                .and_then(|path| path.strip_prefix("/dev/dsk/"))
+        let di = DevInfo::builder().driver(driver).build()?;
+        let mut out = BTreeMap::new();
+        for n in di.walk_driver(driver) {
+            let n = n?;
+            let Some(instance) =
+                n.instance().and_then(|i| u32::try_from(i).ok())
             else {
-                unsafe { free(res as *mut c_void) };
                 continue;
             };
 
-            let out = Some(
+            let mut minors = BTreeMap::new();
+            for m in n.minors() {
+                let m = m?;
+                minors.insert(m.name(), m.devfs_path_full()?);
+            }
+
+            let nam = ["wd", "a"].iter().find_map(|minor| {
+                names.get(minors.get(*minor)?).map(String::as_str)
+            });
+            if let Some(nam) = nam {
                 /*
                  * If we used the "a" minor, strip the slice ("s0") from the
                  * end:
                  */
-                if let Some(dev) = nam.strip_suffix("s0") { dev } else { nam }
-                    .to_string(),
-            );
-
-            unsafe { free(res as *mut c_void) };
-            return out;
+                let nam = nam.strip_suffix("s0").unwrap_or(nam);
+                out.insert(instance, nam.to_string());
+            }
         }
 
-        None
+        Ok(out)
     }
 }
 
+/*
+ * The handle holds state that is updated by lookups, so it must not be used
+ * from more than one thread at a time.
+ */
+unsafe impl Send for DevInstMinor {}
+
 impl Drop for DevInstMinor {
     fn drop(&mut self) {
         unsafe { di_dim_fini(self.handle) }
@@ -0,0 +1,982 @@
+/*
+ * Copyright 2024 Oxide Computer Company
+ */
+
+/*
+ * Disks, as presented by target drivers such as sd(4D) and blkdev(4D) using
+ * the common disk label support in cmlb.  Each disk node has a block and a
+ * character minor node for each slice and fdisk partition, named after the
+ * slice or partition: "a" through "p" for slices 0 through 15, "q" through
+ * "u" for partitions 0 through 4, "p5" and up for extended partitions, and
+ * "wd" for the whole of a disk with an EFI label.  Character minor names have
+ * a ",raw" suffix.
+ */
+
+use crate::nodetype::{MinorNodeFamily, DDI_NT_BLOCK_FABRIC, DDI_NT_BLOCK_SAS};
+use crate::{DevInfo, DevLinks, DiskName, EnclosureMap, Minor, Node, SpecType};
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+const DISK_NSLICES: u32 = 16;
+const DISK_NPRIMARY: u32 = 5;
+
+/**
+ * What part of a disk a minor node refers to.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DiskMinorKind {
+    /**
+     * The whole disk, for a disk with an EFI label ("wd").
+     */
+    WholeDisk,
+    /**
+     * A slice of the label (VTOC or EFI); e.g., Slice(0) for "s0".
+     */
+    Slice(u32),
+    /**
+     * An fdisk partition; e.g., Partition(0) for "p0", which is the whole
+     * disk on x86 systems.
+     */
+    Partition(u32),
+}
+
+impl DiskMinorKind {
+    /**
+     * Classify a minor name; e.g., "a" or "a,raw" is Slice(0).  Returns
+     * None if the name is not one used for disk slices or partitions.
+     */
+    pub fn from_minor_name(name: &str) -> Option<Self> {
+        let name = name.strip_suffix(",raw").unwrap_or(name);
+
+        if name == "wd" {
+            return Some(DiskMinorKind::WholeDisk);
+        }
+
+        /*
+         * Extended partitions have names like "p5"; note that "p" alone is
+         * slice 15.
+         */
+        if let Some(n) = name.strip_prefix('p').filter(|n| !n.is_empty()) {
+            let n: u32 = n.parse().ok()?;
+            return (n >= DISK_NPRIMARY).then_some(DiskMinorKind::Partition(n));
+        }
+
+        let [c] = name.as_bytes() else {
+            return None;
+        };
+        let n = u32::from(c.checked_sub(b'a')?);
+        if n < DISK_NSLICES {
+            Some(DiskMinorKind::Slice(n))
+        } else if n < DISK_NSLICES + DISK_NPRIMARY {
+            Some(DiskMinorKind::Partition(n - DISK_NSLICES))
+        } else {
+            None
+        }
+    }
+
+    /**
+     * The suffix used for this part of the disk in /dev names; e.g., "s0",
+     * "p1", or "" for the whole disk.
+     */
+    pub fn dev_suffix(&self) -> String {
+        match self {
+            DiskMinorKind::WholeDisk => String::new(),
+            DiskMinorKind::Slice(n) => format!("s{}", n),
+            DiskMinorKind::Partition(n) => format!("p{}", n),
+        }
+    }
+}
+
+/**
+ * The kind of a stable disk identifier.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DiskIdKind {
+    /**
+     * The encoded device ID, which is derived from the identity of the
+     * device itself (e.g., its WWN or serial number), and follows it to
+     * another controller or slot.
+     */
+    Devid,
+    /**
+     * The World Wide Name of the logical unit, where one is available.
+     */
+    Wwn,
+    /**
+     * The physical (/devices) path, which identifies the slot or port
+     * rather than the device, and so is stable as long as the hardware is
+     * not reconfigured.
+     */
+    Path,
+}
+
+/**
+ * An identifier for a disk that, unlike a cXtYdZ name, does not depend on the
+ * order in which controllers were discovered.  These are modelled on the
+ * links in /dev/disk/by-id and /dev/disk/by-path on Linux systems.
+ */
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DiskId {
+    pub kind: DiskIdKind,
+    pub value: String,
+}
+
+impl DiskId {
+    /**
+     * A name for the identifier that is suitable for use as a file name or
+     * for persisting; e.g., "devid-id1,sd@n5000c500a1b2c3d4",
+     * "wwn-0x5000c500a1b2c3d4", or "path-pci@0,0-pci1022,1483@1,1-disk@0".
+     */
+    pub fn name(&self) -> String {
+        match self.kind {
+            DiskIdKind::Devid => format!("devid-{}", self.value),
+            DiskIdKind::Wwn => format!("wwn-0x{}", self.value),
+            DiskIdKind::Path => format!(
+                "path-{}",
+                self.value.trim_start_matches('/').replace('/', "-")
+            ),
+        }
+    }
+
+    /**
+     * The directory in which the equivalent link would be found on a Linux
+     * system; i.e., "by-id" or "by-path".
+     */
+    pub fn category(&self) -> &'static str {
+        match self.kind {
+            DiskIdKind::Devid | DiskIdKind::Wwn => "by-id",
+            DiskIdKind::Path => "by-path",
+        }
+    }
+}
+
+impl std::fmt::Display for DiskId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.category(), self.name())
+    }
+}
+
+/**
+ * The type of a disk, as displayed by diskinfo(8), which is determined by the
+ * controller to which the disk is attached.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum DiskType {
+    Ata,
+    Sata,
+    Scsi,
+    Nvme,
+    Usb,
+    Xen,
+    Unknown,
+}
+
+impl std::fmt::Display for DiskType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            DiskType::Ata => "ATA",
+            DiskType::Sata => "SATA",
+            DiskType::Scsi => "SCSI",
+            DiskType::Nvme => "NVME",
+            DiskType::Usb => "USB",
+            DiskType::Xen => "XEN",
+            DiskType::Unknown => "UNKNOWN",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/*
+ * Drivers for controllers of each type, checked against the ancestors of the
+ * disk node from nearest to furthest.
+ */
+const DISK_TYPE_DRIVERS: &[(&str, DiskType)] = &[
+    ("nvme", DiskType::Nvme),
+    ("scsa2usb", DiskType::Usb),
+    ("ahci", DiskType::Sata),
+    ("si3124", DiskType::Sata),
+    ("nv_sata", DiskType::Sata),
+    ("marvell88sx", DiskType::Sata),
+    ("ata", DiskType::Ata),
+    ("xdf", DiskType::Xen),
+    ("xpvd", DiskType::Xen),
+];
+
+/**
+ * How a disk is attached to the system.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum DiskTransport {
+    Iscsi,
+    FibreChannel,
+    Sas,
+    Sata,
+    Ata,
+    Nvme,
+    Usb,
+    /**
+     * A disk provided by a hypervisor; e.g., vioblk(4D).
+     */
+    Virtual,
+    Unknown,
+}
+
+impl DiskTransport {
+    /**
+     * Returns true if the disk is reached over a network or storage fabric,
+     * and so may be shared with other systems, rather than being local
+     * storage.
+     */
+    pub fn is_remote(&self) -> bool {
+        matches!(self, DiskTransport::Iscsi | DiskTransport::FibreChannel)
+    }
+}
+
+/*
+ * Drivers for HBAs and nexus drivers that determine the transport of the
+ * disks beneath them.
+ */
+const DISK_TRANSPORT_DRIVERS: &[(&str, DiskTransport)] = &[
+    ("iscsi", DiskTransport::Iscsi),
+    ("fp", DiskTransport::FibreChannel),
+    ("fcp", DiskTransport::FibreChannel),
+    ("fcoei", DiskTransport::FibreChannel),
+    ("qlc", DiskTransport::FibreChannel),
+    ("emlxs", DiskTransport::FibreChannel),
+    ("mpt_sas", DiskTransport::Sas),
+    ("pmcs", DiskTransport::Sas),
+    ("lmrc", DiskTransport::Sas),
+    ("ahci", DiskTransport::Sata),
+    ("si3124", DiskTransport::Sata),
+    ("nv_sata", DiskTransport::Sata),
+    ("marvell88sx", DiskTransport::Sata),
+    ("ata", DiskTransport::Ata),
+    ("nvme", DiskTransport::Nvme),
+    ("scsa2usb", DiskTransport::Usb),
+    ("vioblk", DiskTransport::Virtual),
+    ("vioscsi", DiskTransport::Virtual),
+    ("pvscsi", DiskTransport::Virtual),
+    ("xdf", DiskTransport::Virtual),
+    ("xpvd", DiskTransport::Virtual),
+];
+
+/*
+ * Look for a recognised HBA driver on a node or any of its ancestors.  SAS
+ * HBAs other than those listed present each initiator port as an "iport"
+ * node, which is recognised as well.
+ */
+fn transport_of_ancestors(node: Option<Node>) -> Result<Option<DiskTransport>> {
+    let mut n = node;
+    while let Some(p) = n {
+        if let Some(drv) = p.driver_name() {
+            if let Some((_, t)) =
+                DISK_TRANSPORT_DRIVERS.iter().find(|(d, _)| *d == drv)
+            {
+                return Ok(Some(*t));
+            }
+        }
+        if p.node_name() == "iport" {
+            return Ok(Some(DiskTransport::Sas));
+        }
+        n = p.parent()?;
+    }
+    Ok(None)
+}
+
+/*
+ * Return the name of the slot that holds the given child of a PCI bridge, if
+ * the bridge has a "slot-names" property that names one.
+ */
+fn slot_name(bridge: &Node, child: &Node) -> Option<String> {
+    let prop = bridge.prop_bytes("slot-names")?;
+    slot_names_lookup(prop, &child.unit_address()?)
+}
+
+/*
+ * The "slot-names" property of a PCI bridge is a mask of the device numbers
+ * that have slots, followed by a NUL-terminated name for each set bit, in
+ * ascending order.  Return the name for the device with the given unit
+ * address (e.g., "3" or "3,1"), if any.
+ */
+fn slot_names_lookup(prop: &[u8], addr: &str) -> Option<String> {
+    let (mask, mut names) = prop.split_first_chunk::<4>()?;
+    let mask = u32::from_ne_bytes(*mask);
+
+    let dev = addr.split(',').next()?;
+    let dev = u32::from_str_radix(dev, 16).ok().filter(|d| *d < 32)?;
+    if mask & (1 << dev) == 0 {
+        return None;
+    }
+
+    for bit in 0..=dev {
+        if mask & (1 << bit) == 0 {
+            continue;
+        }
+        let len = names.iter().position(|c| *c == 0)?;
+        let (name, rest) = names.split_at(len);
+        if bit == dev {
+            let name = String::from_utf8_lossy(name).trim().to_string();
+            return (!name.is_empty()).then_some(name);
+        }
+        names = &rest[1..];
+    }
+    None
+}
+
+/*
+ * Find the PCIe slot that holds the given node or one of its ancestors; e.g.,
+ * the NVMe controller of a namespace, or the HBA above a SAS disk.  For each
+ * node on the way up, the slot is named by the "slot-names" property of the
+ * bridge above it, then by the hotplug connector it occupies (which requires
+ * a snapshot taken with SnapshotFlags::HP), and then by the slot number in
+ * the "physical-slot#" property of the bridge.
+ */
+fn pci_slot(node: &Node) -> Result<Option<String>> {
+    let mut child = node.clone();
+    while let Some(parent) = child.parent()? {
+        if let Some(name) = slot_name(&parent, &child) {
+            return Ok(Some(name));
+        }
+
+        for hp in parent.hotplug_connectors() {
+            let hp = hp?;
+            if hp.occupant().is_some_and(|o| o.node == child.node) {
+                return Ok(Some(hp.name()));
+            }
+        }
+
+        if let Some(n) = parent.prop_i32("physical-slot#") {
+            return Ok(Some(format!("Slot {}", n)));
+        }
+
+        child = parent;
+    }
+    Ok(None)
+}
+
+/**
+ * The information about a disk that is displayed by diskinfo(8).  Fields
+ * that the driver does not publish as properties are None.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DiskInfo {
+    pub disk_type: DiskType,
+    /**
+     * The public name of the disk; e.g., "c1t0d0".
+     */
+    pub name: Option<String>,
+    pub devfs_path: String,
+    pub vendor: Option<String>,
+    pub product: Option<String>,
+    pub serial: Option<String>,
+    /**
+     * The capacity of the disk, in bytes.
+     */
+    pub size: Option<u64>,
+    /**
+     * The logical block size of the disk, in bytes.
+     */
+    pub block_size: Option<u32>,
+    pub removable: bool,
+    pub solid_state: Option<bool>,
+    /**
+     * The physical location of the disk, as with Disk::physical_location().
+     */
+    pub location: Option<String>,
+}
+
+/**
+ * A controller node (e.g., an HBA, SAS iport, or NVMe controller), along
+ * with the disks attached to it.
+ */
+pub struct DiskController<'a> {
+    pub node: Node<'a>,
+    /**
+     * The controller number used in the public names of the disks; e.g., 1
+     * for "c1t0d0".  This is None if none of the disks have links in
+     * /dev/dsk.
+     */
+    pub number: Option<u32>,
+    pub disks: Vec<Disk<'a>>,
+}
+
+impl std::fmt::Debug for DiskController<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DiskController")
+            .field("devfs_path", &self.node.devfs_path().ok())
+            .field("number", &self.number)
+            .field("disks", &self.disks)
+            .finish()
+    }
+}
+
+impl DevInfo {
+    /**
+     * Group the disks in the snapshot by the controller to which each is
+     * attached, ordered by controller number.
+     */
+    pub fn disk_controllers(
+        &self,
+        links: &DevLinks,
+    ) -> Result<Vec<DiskController<'_>>> {
+        let mut ctlrs: BTreeMap<String, DiskController> = BTreeMap::new();
+        for d in self.disks()? {
+            let Some(node) = d.controller()? else {
+                continue;
+            };
+            let number = d.controller_number(links)?;
+
+            let ctlr = ctlrs.entry(node.devfs_path()?).or_insert_with(|| {
+                DiskController { node, number: None, disks: Vec::new() }
+            });
+            ctlr.number = ctlr.number.or(number);
+            ctlr.disks.push(d);
+        }
+
+        /*
+         * Controllers without a number sort last.
+         */
+        let mut out = ctlrs.into_values().collect::<Vec<_>>();
+        out.sort_by_key(|c| c.number.map_or((1, 0), |n| (0, n)));
+        Ok(out)
+    }
+
+    /**
+     * Collect the diskinfo(8) fields for every disk in the snapshot, as with
+     * Disk::info().
+     */
+    pub fn disk_info(&self, links: &DevLinks) -> Result<Vec<DiskInfo>> {
+        let enclosures = self.enclosure_map()?;
+        self.disks()?.iter().map(|d| d.info(links, &enclosures)).collect()
+    }
+
+    /**
+     * Collect every disk in the snapshot; i.e., every node with block minor
+     * nodes.  The snapshot must include minor nodes.
+     */
+    pub fn disks(&self) -> Result<Vec<Disk<'_>>> {
+        let mut out = Vec::new();
+        for n in self.walk_node() {
+            if let Some(d) = Disk::from_node(n?)? {
+                out.push(d);
+            }
+        }
+        Ok(out)
+    }
+
+    /**
+     * Find a disk by the name of one of its stable identifiers, as produced
+     * by DiskId::name(); e.g., "wwn-0x5000c500a1b2c3d4".
+     */
+    pub fn disk_by_id_name(&self, name: &str) -> Result<Option<Disk<'_>>> {
+        for d in self.disks()? {
+            if d.stable_ids()?.iter().any(|id| id.name() == name) {
+                return Ok(Some(d));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/**
+ * A disk node, with typed access to its slices and partitions.
+ */
+#[derive(Clone)]
+pub struct Disk<'a> {
+    node: Node<'a>,
+}
+
+/**
+ * A minor node of a disk, along with the part of the disk to which it
+ * refers.
+ */
+pub struct DiskMinor<'a> {
+    pub minor: Minor<'a>,
+    pub kind: DiskMinorKind,
+}
+
+impl DiskMinor<'_> {
+    /**
+     * Returns true if this is the character (raw) device, as used in
+     * /dev/rdsk, rather than the block device.
+     */
+    pub fn is_raw(&self) -> bool {
+        self.minor.spec_type() == SpecType::Char
+    }
+}
+
+impl std::fmt::Debug for DiskMinor<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DiskMinor")
+            .field("name", &self.minor.name())
+            .field("kind", &self.kind)
+            .field("raw", &self.is_raw())
+            .finish()
+    }
+}
+
+impl<'a> Disk<'a> {
+    /**
+     * Returns None if the node has no block minor nodes, and is thus not a
+     * disk.
+     */
+    pub fn from_node(node: Node<'a>) -> Result<Option<Self>> {
+        for m in node.minors() {
+            let m = m?;
+            if m.spec_type() == SpecType::Block
+                && m.node_type_parsed().family() == &MinorNodeFamily::Block
+            {
+                return Ok(Some(Disk { node }));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn node(&self) -> &Node<'a> {
+        &self.node
+    }
+
+    /**
+     * Collect the minor nodes of the disk that refer to slices, partitions,
+     * or the whole disk, in both block and character form.
+     */
+    pub fn minors(&self) -> Result<Vec<DiskMinor<'a>>> {
+        let mut out = Vec::new();
+        for m in self.node.minors() {
+            let m = m?;
+            if let Some(kind) = DiskMinorKind::from_minor_name(&m.name()) {
+                out.push(DiskMinor { minor: m, kind });
+            }
+        }
+        Ok(out)
+    }
+
+    fn find(
+        &self,
+        kind: DiskMinorKind,
+        raw: bool,
+    ) -> Result<Option<Minor<'a>>> {
+        Ok(self
+            .minors()?
+            .into_iter()
+            .find(|m| m.kind == kind && m.is_raw() == raw)
+            .map(|m| m.minor))
+    }
+
+    /**
+     * The minor node for the whole disk, if the disk has an EFI label.
+     */
+    pub fn whole_disk(&self, raw: bool) -> Result<Option<Minor<'a>>> {
+        self.find(DiskMinorKind::WholeDisk, raw)
+    }
+
+    pub fn slice(&self, n: u32, raw: bool) -> Result<Option<Minor<'a>>> {
+        self.find(DiskMinorKind::Slice(n), raw)
+    }
+
+    pub fn partition(&self, n: u32, raw: bool) -> Result<Option<Minor<'a>>> {
+        self.find(DiskMinorKind::Partition(n), raw)
+    }
+
+    /**
+     * The minor nodes for each slice, keyed by slice number.
+     */
+    pub fn slices(&self, raw: bool) -> Result<BTreeMap<u32, Minor<'a>>> {
+        Ok(self
+            .minors()?
+            .into_iter()
+            .filter(|m| m.is_raw() == raw)
+            .filter_map(|m| match m.kind {
+                DiskMinorKind::Slice(n) => Some((n, m.minor)),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /**
+     * The minor nodes for each fdisk partition, keyed by partition number.
+     */
+    pub fn partitions(&self, raw: bool) -> Result<BTreeMap<u32, Minor<'a>>> {
+        Ok(self
+            .minors()?
+            .into_iter()
+            .filter(|m| m.is_raw() == raw)
+            .filter_map(|m| match m.kind {
+                DiskMinorKind::Partition(n) => Some((n, m.minor)),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /**
+     * Determine the public name of the disk (e.g., "c1t0d0") from the links
+     * in /dev/dsk for its minor nodes.
+     */
+    pub fn name(&self, links: &DevLinks) -> Result<Option<DiskName>> {
+        for m in self.minors()? {
+            if m.is_raw() {
+                continue;
+            }
+
+            for l in links.links_for_path(m.minor.devfs_path()?)? {
+                if !l.path().starts_with(Path::new("/dev/dsk")) {
+                    continue;
+                }
+                if let Ok(name) = DiskName::from_dev_path(l.path()) {
+                    return Ok(Some(name.whole_disk()));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /**
+     * The controller node to which the disk is attached; i.e., its parent,
+     * to which devfsadm(8) assigns the controller number used in the public
+     * name of the disk.  For a multipath disk under scsi_vhci(4D), this is the
+     * scsi_vhci node.
+     */
+    pub fn controller(&self) -> Result<Option<Node<'a>>> {
+        self.node.parent()
+    }
+
+    /**
+     * The controller number in the public name of the disk; e.g., 1 for
+     * "c1t0d0".
+     */
+    pub fn controller_number(&self, links: &DevLinks) -> Result<Option<u32>> {
+        Ok(self.name(links)?.map(|n| n.controller()))
+    }
+
+    /**
+     * The type of the disk, as determined by the driver of the nearest
+     * controller that is recognised.  Otherwise, disks attached via sd(4D)
+     * are SCSI disks.
+     */
+    pub fn disk_type(&self) -> Result<DiskType> {
+        let mut n = self.node.parent()?;
+        while let Some(p) = n {
+            if let Some(drv) = p.driver_name() {
+                if let Some((_, t)) =
+                    DISK_TYPE_DRIVERS.iter().find(|(d, _)| *d == drv)
+                {
+                    return Ok(*t);
+                }
+            }
+            n = p.parent()?;
+        }
+
+        if self.node.driver_name().as_deref() == Some("sd") {
+            Ok(DiskType::Scsi)
+        } else {
+            Ok(DiskType::Unknown)
+        }
+    }
+
+    /**
+     * Determine how the disk is attached; e.g., to exclude iSCSI and Fibre
+     * Channel disks from an inventory of local storage.  The transport is
+     * taken from the node type of the disk's minor nodes where the target
+     * driver is specific, and otherwise from the driver of the HBA above it.
+     * For a multipath client device under scsi_vhci(4D), the HBAs of its
+     * paths are examined instead, which requires a snapshot taken with
+     * SnapshotFlags::PATH.
+     */
+    pub fn transport(&self) -> Result<DiskTransport> {
+        for m in self.node.minors() {
+            let t = m?.node_type_parsed();
+            if t.is_a(DDI_NT_BLOCK_FABRIC) {
+                return Ok(DiskTransport::FibreChannel);
+            }
+            if t.is_a(DDI_NT_BLOCK_SAS) {
+                return Ok(DiskTransport::Sas);
+            }
+        }
+
+        if let Some(t) = transport_of_ancestors(self.node.parent()?)? {
+            return Ok(t);
+        }
+
+        for p in self.node.multipath_phcis()? {
+            if let Some(t) = transport_of_ancestors(Some(p.phci))? {
+                return Ok(t);
+            }
+        }
+
+        Ok(DiskTransport::Unknown)
+    }
+
+    /**
+     * Describe where the disk is physically installed; e.g., "Slot 7" or
+     * "Backplane A bay 3, controller in Slot 2".  The map of enclosures can
+     * be built once, with DevInfo::enclosure_map(), for any number of disks.
+     *
+     * For a disk behind an SES enclosure, the location includes the
+     * enclosure, along with the bay where the HBA driver publishes a slot
+     * number, or otherwise the expander PHY to which the disk is attached
+     * (which is not a bay number; see the enclosure module).  It also
+     * includes the PCIe slot that holds the device (for an NVMe device) or
+     * its controller (otherwise), if any, as named by the bridge above it or
+     * by the hotplug connector it occupies (which requires a snapshot taken
+     * with SnapshotFlags::HP).  Returns None if the location cannot be
+     * determined.
+     */
+    pub fn physical_location(
+        &self,
+        enclosures: &EnclosureMap,
+    ) -> Result<Option<String>> {
+        let mut parts = Vec::new();
+
+        if let Some((e, b)) = enclosures.bay_of(self) {
+            let name = e.product().unwrap_or_else(|| "Enclosure".into());
+            parts.push(match (b.slot, b.phy) {
+                (Some(slot), _) => format!("{} bay {}", name, slot),
+                (None, Some(phy)) => format!("{} PHY {}", name, phy),
+                (None, None) => name,
+            });
+        }
+
+        if let Some(slot) = pci_slot(&self.node)? {
+            parts.push(if self.transport()? == DiskTransport::Nvme {
+                slot
+            } else {
+                format!("controller in {}", slot)
+            });
+        }
+
+        Ok((!parts.is_empty()).then(|| parts.join(", ")))
+    }
+
+    /**
+     * Collect the fields displayed by diskinfo(8) from the properties of the
+     * disk node, without needing to open the device.  The map of enclosures
+     * is used to determine the location, as with physical_location().
+     */
+    pub fn info(
+        &self,
+        links: &DevLinks,
+        enclosures: &EnclosureMap,
+    ) -> Result<DiskInfo> {
+        let n = &self.node;
+        let prop_str = |name: &str| {
+            n.prop_str(name)
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+        };
+
+        let block_size = n
+            .prop_i32("device-blksize")
+            .and_then(|b| u32::try_from(b).ok())
+            .filter(|b| *b > 0);
+        let size = n
+            .prop_i64("device-nblocks")
+            .and_then(|b| u64::try_from(b).ok())
+            .map(|b| b * u64::from(block_size.unwrap_or(512)));
+
+        Ok(DiskInfo {
+            disk_type: self.disk_type()?,
+            name: self.name(links)?.map(|n| n.to_string()),
+            devfs_path: n.devfs_path()?,
+            vendor: prop_str("inquiry-vendor-id"),
+            product: prop_str("inquiry-product-id"),
+            serial: prop_str("inquiry-serial-no"),
+            size,
+            block_size,
+            removable: n.prop_bool("removable-media"),
+            solid_state: n.prop_i32("device-solid-state").map(|v| v != 0),
+            location: self.physical_location(enclosures)?,
+        })
+    }
+
+    /**
+     * The World Wide Name of the logical unit, as a string of hexadecimal
+     * digits.  This is taken from the "wwn" property where the driver
+     * provides one, or else from a unit address or device ID that is based
+     * on the WWN, as used by scsi_vhci(4D) and sd(4D) respectively.
+     */
+    pub fn wwn(&self) -> Option<String> {
+        if let Some(b) = self.node.prop_bytes("wwn").filter(|b| !b.is_empty()) {
+            return Some(b.iter().map(|b| format!("{:02x}", b)).collect());
+        }
+
+        let is_wwn = |s: &&str| {
+            !s.is_empty() && s.chars().all(|c| c.is_ascii_hexdigit())
+        };
+
+        if let Some(w) = self.node.unit_address().and_then(|a| {
+            a.strip_prefix('g').filter(is_wwn).map(str::to_ascii_lowercase)
+        }) {
+            return Some(w);
+        }
+
+        let devid = self.node.devid()?;
+        let (_, id) = devid.split_once('@')?;
+        id.strip_prefix('n').filter(is_wwn).map(str::to_ascii_lowercase)
+    }
+
+    /**
+     * Generate each of the stable identifiers that are available for this
+     * disk, in order of preference: the device ID, the WWN, and then the
+     * physical path, which is always available.
+     */
+    pub fn stable_ids(&self) -> Result<Vec<DiskId>> {
+        let mut out = Vec::new();
+        if let Some(devid) = self.node.devid() {
+            out.push(DiskId { kind: DiskIdKind::Devid, value: devid });
+        }
+        if let Some(wwn) = self.wwn() {
+            out.push(DiskId { kind: DiskIdKind::Wwn, value: wwn });
+        }
+        out.push(DiskId {
+            kind: DiskIdKind::Path,
+            value: self.node.devfs_path()?,
+        });
+        Ok(out)
+    }
+}
+
+impl std::fmt::Debug for Disk<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Disk")
+            .field("devfs_path", &self.node.devfs_path().ok())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn minor_kind() {
+        use DiskMinorKind::*;
+
+        for (name, kind) in [
+            ("wd", Some(WholeDisk)),
+            ("wd,raw", Some(WholeDisk)),
+            ("a", Some(Slice(0))),
+            ("a,raw", Some(Slice(0))),
+            ("h", Some(Slice(7))),
+            /*
+             * "p" alone is the last slice, not a partition.
+             */
+            ("p", Some(Slice(15))),
+            ("p,raw", Some(Slice(15))),
+            ("q", Some(Partition(0))),
+            ("u,raw", Some(Partition(4))),
+            ("p5", Some(Partition(5))),
+            ("p32,raw", Some(Partition(32))),
+            /*
+             * The primary partitions are named "q" through "u", so these
+             * names are not used.
+             */
+            ("p0", None),
+            ("p4,raw", None),
+            ("v", None),
+            ("A", None),
+            ("ab", None),
+            ("px", None),
+            ("", None),
+            (",raw", None),
+            ("a,raw,raw", None),
+            ("wd,blk", None),
+        ] {
+            assert_eq!(
+                DiskMinorKind::from_minor_name(name),
+                kind,
+                "{:?}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn dev_suffix() {
+        assert_eq!(DiskMinorKind::WholeDisk.dev_suffix(), "");
+        assert_eq!(DiskMinorKind::Slice(0).dev_suffix(), "s0");
+        assert_eq!(DiskMinorKind::Slice(15).dev_suffix(), "s15");
+        assert_eq!(DiskMinorKind::Partition(0).dev_suffix(), "p0");
+        assert_eq!(DiskMinorKind::Partition(5).dev_suffix(), "p5");
+
+        /*
+         * The suffix for each minor name is that used in /dev.
+         */
+        for (name, suffix) in [("a", "s0"), ("p", "s15"), ("q", "p0")] {
+            let kind = DiskMinorKind::from_minor_name(name).unwrap();
+            assert_eq!(kind.dev_suffix(), suffix);
+        }
+    }
+
+    fn slot_names(mask: u32, names: &[&str]) -> Vec<u8> {
+        let mut out = mask.to_ne_bytes().to_vec();
+        for n in names {
+            out.extend_from_slice(n.as_bytes());
+            out.push(0);
+        }
+        out
+    }
+
+    #[test]
+    fn slot_names_mask() {
+        /*
+         * Devices 1, 4, and 31 have slots.
+         */
+        let prop = slot_names(
+            (1 << 1) | (1 << 4) | (1 << 31),
+            &["Slot 1", "Slot 4", "Slot 31"],
+        );
+        assert_eq!(slot_names_lookup(&prop, "1"), Some("Slot 1".into()));
+        assert_eq!(slot_names_lookup(&prop, "4,2"), Some("Slot 4".into()));
+        assert_eq!(slot_names_lookup(&prop, "1f"), Some("Slot 31".into()));
+
+        /*
+         * A device whose bit is unset has no slot, even though there are
+         * names for devices on either side of it.
+         */
+        assert_eq!(slot_names_lookup(&prop, "0"), None);
+        assert_eq!(slot_names_lookup(&prop, "2"), None);
+        assert_eq!(slot_names_lookup(&prop, "5"), None);
+    }
+
+    #[test]
+    fn slot_names_malformed() {
+        /*
+         * The last name is missing its NUL terminator.
+         */
+        let mut prop = slot_names((1 << 0) | (1 << 2), &["A"]);
+        prop.extend_from_slice(b"B");
+        assert_eq!(slot_names_lookup(&prop, "0"), Some("A".into()));
+        assert_eq!(slot_names_lookup(&prop, "2"), None);
+
+        /*
+         * There are fewer names than bits in the mask.
+         */
+        let prop = slot_names((1 << 0) | (1 << 2), &["A"]);
+        assert_eq!(slot_names_lookup(&prop, "2"), None);
+
+        /*
+         * A device number of 32 or more cannot appear in the mask.
+         */
+        let prop = slot_names(u32::MAX, &["A"; 32]);
+        assert_eq!(slot_names_lookup(&prop, "1f"), Some("A".into()));
+        assert_eq!(slot_names_lookup(&prop, "20"), None);
+        assert_eq!(slot_names_lookup(&prop, "100000000"), None);
+
+        /*
+         * An empty name, a short property, or a bad address names nothing.
+         */
+        assert_eq!(slot_names_lookup(&slot_names(1, &[""]), "0"), None);
+        assert_eq!(slot_names_lookup(&[1, 0, 0], "0"), None);
+        assert_eq!(slot_names_lookup(&slot_names(1, &["A"]), "x"), None);
+        assert_eq!(slot_names_lookup(&slot_names(1, &["A"]), ""), None);
+    }
+}
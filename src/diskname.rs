@@ -0,0 +1,240 @@
+/*
+ * Copyright 2024 Oxide Computer Company
+ */
+
+use anyhow::{bail, Result};
+use std::path::Path;
+use std::str::FromStr;
+
+/**
+ * A parsed public disk name, as found in /dev/dsk and /dev/rdsk; e.g.,
+ * "c1t0025385C9150D623d0s0".  The name is made up of a controller number, an
+ * optional target (which may be a number or a hexadecimal WWN), a disk (LUN)
+ * number, and optionally either a slice ("sN") or an fdisk partition ("pN").
+ */
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DiskName {
+    controller: u32,
+    target: Option<String>,
+    disk: u32,
+    slice: Option<u32>,
+    partition: Option<u32>,
+}
+
+impl DiskName {
+    /**
+     * Parse the final component of a /dev path; e.g., "/dev/dsk/c1t0d0s0" or
+     * "/dev/rdsk/c1t0d0p0".
+     */
+    pub fn from_dev_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            bail!("{} does not end in a disk name", path.display());
+        };
+        name.parse()
+    }
+
+    pub fn controller(&self) -> u32 {
+        self.controller
+    }
+
+    pub fn target(&self) -> Option<&str> {
+        self.target.as_deref()
+    }
+
+    pub fn disk(&self) -> u32 {
+        self.disk
+    }
+
+    pub fn slice(&self) -> Option<u32> {
+        self.slice
+    }
+
+    pub fn partition(&self) -> Option<u32> {
+        self.partition
+    }
+
+    /**
+     * The name of the whole disk, without any slice or partition; e.g.,
+     * "c1t0d0" for "c1t0d0s0".
+     */
+    pub fn whole_disk(&self) -> DiskName {
+        DiskName { slice: None, partition: None, ..self.clone() }
+    }
+
+    /**
+     * The name of the given slice on this disk.
+     */
+    pub fn with_slice(&self, slice: u32) -> DiskName {
+        DiskName { slice: Some(slice), partition: None, ..self.clone() }
+    }
+}
+
+impl std::fmt::Display for DiskName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "c{}", self.controller)?;
+        if let Some(target) = &self.target {
+            write!(f, "t{}", target)?;
+        }
+        write!(f, "d{}", self.disk)?;
+        if let Some(slice) = self.slice {
+            write!(f, "s{}", slice)?;
+        }
+        if let Some(partition) = self.partition {
+            write!(f, "p{}", partition)?;
+        }
+        Ok(())
+    }
+}
+
+/*
+ * Split a leading run of characters that match the predicate from the rest of
+ * the string.
+ */
+fn take_while(s: &str, pred: impl Fn(char) -> bool) -> (&str, &str) {
+    let end = s.find(|c: char| !pred(c)).unwrap_or(s.len());
+    s.split_at(end)
+}
+
+fn take_number<'a>(s: &'a str, orig: &str) -> Result<(u32, &'a str)> {
+    let (num, rest) = take_while(s, |c| c.is_ascii_digit());
+    let Ok(num) = num.parse() else {
+        bail!("disk name {:?} has an invalid number", orig);
+    };
+    Ok((num, rest))
+}
+
+impl FromStr for DiskName {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let Some(rest) = s.strip_prefix('c') else {
+            bail!("disk name {:?} does not begin with a controller", s);
+        };
+        let (controller, rest) = take_number(rest, s)?;
+
+        /*
+         * Targets are either decimal numbers or WWNs in upper case
+         * hexadecimal, so they cannot contain the "d" that introduces the
+         * disk number.
+         */
+        let (target, rest) = if let Some(rest) = rest.strip_prefix('t') {
+            let (target, rest) = take_while(rest, |c| {
+                c.is_ascii_digit() || ('A'..='F').contains(&c)
+            });
+            if target.is_empty() {
+                bail!("disk name {:?} has an empty target", s);
+            }
+            (Some(target.to_string()), rest)
+        } else {
+            (None, rest)
+        };
+
+        let Some(rest) = rest.strip_prefix('d') else {
+            bail!("disk name {:?} does not have a disk number", s);
+        };
+        let (disk, rest) = take_number(rest, s)?;
+
+        let (slice, partition, rest) =
+            if let Some(rest) = rest.strip_prefix('s') {
+                let (n, rest) = take_number(rest, s)?;
+                (Some(n), None, rest)
+            } else if let Some(rest) = rest.strip_prefix('p') {
+                let (n, rest) = take_number(rest, s)?;
+                (None, Some(n), rest)
+            } else {
+                (None, None, rest)
+            };
+
+        if !rest.is_empty() {
+            bail!("disk name {:?} has trailing characters {:?}", s, rest);
+        }
+
+        Ok(DiskName { controller, target, disk, slice, partition })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(s: &str) -> DiskName {
+        let d: DiskName = s.parse().unwrap();
+        assert_eq!(d.to_string(), s);
+        d
+    }
+
+    #[test]
+    fn slice() {
+        let d = parse("c1t0d0s0");
+        assert_eq!(d.controller(), 1);
+        assert_eq!(d.target(), Some("0"));
+        assert_eq!(d.disk(), 0);
+        assert_eq!(d.slice(), Some(0));
+        assert_eq!(d.partition(), None);
+        assert_eq!(d.whole_disk().to_string(), "c1t0d0");
+        assert_eq!(d.with_slice(7).to_string(), "c1t0d0s7");
+
+        let d = parse("c12t34d5s15");
+        assert_eq!((d.controller(), d.disk(), d.slice()), (12, 5, Some(15)));
+    }
+
+    #[test]
+    fn no_target() {
+        let d = parse("c0d1");
+        assert_eq!(d.controller(), 0);
+        assert_eq!(d.target(), None);
+        assert_eq!(d.disk(), 1);
+        assert_eq!(d.slice(), None);
+        assert_eq!(parse("c0d1p0").partition(), Some(0));
+    }
+
+    #[test]
+    fn partition() {
+        let d = parse("c1t0d0p4");
+        assert_eq!(d.slice(), None);
+        assert_eq!(d.partition(), Some(4));
+        assert_eq!(d.with_slice(0).to_string(), "c1t0d0s0");
+    }
+
+    #[test]
+    fn wwn() {
+        let d = parse("c1t0025385C9150D623d0");
+        assert_eq!(d.target(), Some("0025385C9150D623"));
+        assert_eq!(d.disk(), 0);
+
+        let d = parse("c0t5000C500A1B2C3D4d0s0");
+        assert_eq!(d.target(), Some("5000C500A1B2C3D4"));
+        assert_eq!(d.slice(), Some(0));
+    }
+
+    #[test]
+    fn dev_path() {
+        let d = DiskName::from_dev_path("/dev/rdsk/c1t0d0s2").unwrap();
+        assert_eq!(d.to_string(), "c1t0d0s2");
+        assert!(DiskName::from_dev_path("/dev/rdsk/").is_err());
+        assert!(DiskName::from_dev_path("/dev/rdsk/sd0").is_err());
+    }
+
+    #[test]
+    fn malformed() {
+        for s in [
+            "",
+            "c",
+            "t0d0",
+            "c1",
+            "c1t0",
+            "c1td0",
+            "c1t0d",
+            "cXt0d0",
+            "c1t0d0s",
+            "c1t0d0p",
+            "c1t0d0s0p0",
+            "c1t0d0s0x",
+            "c1t5000c500a1b2c3d4d0",
+            "c99999999999t0d0",
+        ] {
+            assert!(s.parse::<DiskName>().is_err(), "{:?} parsed", s);
+        }
+    }
+}
@@ -0,0 +1,336 @@
+/*
+ * Copyright 2024 Oxide Computer Company
+ */
+
+/*
+ * SCSI enclosure services (SES) devices, as presented by ses(4D).  An
+ * enclosure (e.g., a disk shelf or the backplane of a server) that contains a
+ * SAS expander usually presents an SES target through that expander, so the
+ * disks in the enclosure can be found by looking for disks attached to the
+ * expander.
+ *
+ * This is a heuristic, with limits that consumers should be aware of:
+ *
+ * - The precise mapping of disks to enclosure elements, and the labels of
+ *   the bays, are only available from the SES pages of the device itself
+ *   (e.g., through libses or fmtopo), which are not part of the device tree.
+ *
+ * - Only SAS addressing is used, so disks attached by other means (e.g.,
+ *   SATA disks on an AHCI controller behind a passive backplane) are not
+ *   found.
+ *
+ * - A disk is paired with an enclosure whose SES target has the SAS address
+ *   of the expander to which the disk is attached.  Failing that, a disk
+ *   attached to the same expander as exactly one SES target is paired with
+ *   it.  A disk that could belong to more than one enclosure (e.g., where
+ *   the expanders of several enclosures are cascaded and each SES target is
+ *   reached through the same port) is not paired with any.
+ *
+ * - The bay is only known if the HBA driver publishes a slot or bay number
+ *   for the disk, which few do.  The number of the expander PHY to which the
+ *   disk is attached is often available instead, but the relationship
+ *   between PHYs and bays is particular to each enclosure.
+ */
+
+use crate::nodetype::DDI_NT_ENCLOSURE;
+use crate::{DevInfo, Disk, Minor, Node};
+use anyhow::Result;
+
+/*
+ * The addressing properties of a SAS target, from <sys/scsi/scsi_address.h>.
+ */
+const SCSI_ADDR_PROP_TARGET_PORT: &str = "target-port";
+const SCSI_ADDR_PROP_ATTACHED_PORT: &str = "attached-port";
+const SCSI_ADDR_PROP_PHY: &str = "phy-num";
+
+/*
+ * Properties through which an HBA driver may publish the enclosure slot (or
+ * bay) that holds a disk.
+ */
+const ENCLOSURE_SLOT_PROPS: &[&str] = &["slot", "bay"];
+
+/*
+ * The SAS addresses of a target: its own, and that of the expander (or HBA)
+ * port to which it is attached.  A multipath device may be reached through
+ * several target ports; each is included.
+ */
+#[derive(Default)]
+struct SasPorts {
+    target: Vec<String>,
+    attached: Vec<String>,
+}
+
+fn normalise_port(p: String) -> String {
+    p.trim_start_matches('w').to_ascii_lowercase()
+}
+
+fn sas_ports(n: &Node) -> Result<SasPorts> {
+    let mut ports = SasPorts::default();
+    ports.target.extend(n.prop_str(SCSI_ADDR_PROP_TARGET_PORT));
+    ports.attached.extend(n.prop_str(SCSI_ADDR_PROP_ATTACHED_PORT));
+
+    for p in n.client_paths() {
+        let p = p?;
+        ports.target.extend(p.prop_str(SCSI_ADDR_PROP_TARGET_PORT));
+        ports.attached.extend(p.prop_str(SCSI_ADDR_PROP_ATTACHED_PORT));
+    }
+
+    ports.target = ports.target.into_iter().map(normalise_port).collect();
+    ports.attached = ports.attached.into_iter().map(normalise_port).collect();
+    Ok(ports)
+}
+
+impl DevInfo {
+    /**
+     * Collect every SES enclosure device in the snapshot.  The snapshot must
+     * include minor nodes.
+     */
+    pub fn enclosures(&self) -> Result<Vec<Enclosure<'_>>> {
+        let mut out = Vec::new();
+        for n in self.walk_node() {
+            if let Some(e) = Enclosure::from_node(n?)? {
+                out.push(e);
+            }
+        }
+        Ok(out)
+    }
+
+    /**
+     * Collect every SES enclosure device in the snapshot, and pair each with
+     * the disks behind it, with a single walk of the snapshot.  The snapshot
+     * must include minor nodes.  See the comment at the top of this module
+     * for the limits of the approach.
+     */
+    pub fn enclosure_map(&self) -> Result<EnclosureMap<'_>> {
+        let mut enclosures = Vec::new();
+        let mut disks = Vec::new();
+        for n in self.walk_node() {
+            let n = n?;
+            if let Some(d) = Disk::from_node(n.clone())? {
+                disks.push(d);
+            } else if let Some(e) = Enclosure::from_node(n)? {
+                enclosures.push(e);
+            }
+        }
+        EnclosureMap::new(enclosures, disks)
+    }
+}
+
+/**
+ * An SES enclosure device.
+ */
+#[derive(Clone)]
+pub struct Enclosure<'a> {
+    node: Node<'a>,
+}
+
+impl<'a> Enclosure<'a> {
+    /**
+     * Returns None if the node is not bound to ses(4D) and has no enclosure
+     * minor nodes.
+     */
+    pub fn from_node(node: Node<'a>) -> Result<Option<Self>> {
+        if node.driver_name().as_deref() == Some("ses") {
+            return Ok(Some(Enclosure { node }));
+        }
+
+        for m in node.minors() {
+            if m?.node_type_parsed().is_a(DDI_NT_ENCLOSURE) {
+                return Ok(Some(Enclosure { node }));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn node(&self) -> &Node<'a> {
+        &self.node
+    }
+
+    /**
+     * The enclosure minor nodes, through which SES pages may be read; e.g.,
+     * by libses or fmtopo.
+     */
+    pub fn minors(&self) -> Result<Vec<Minor<'a>>> {
+        let mut out = Vec::new();
+        for m in self.node.minors() {
+            let m = m?;
+            if m.node_type_parsed().is_a(DDI_NT_ENCLOSURE) {
+                out.push(m);
+            }
+        }
+        Ok(out)
+    }
+
+    pub fn vendor(&self) -> Option<String> {
+        self.node.prop_str("inquiry-vendor-id").map(|s| s.trim().to_string())
+    }
+
+    pub fn product(&self) -> Option<String> {
+        self.node.prop_str("inquiry-product-id").map(|s| s.trim().to_string())
+    }
+
+    pub fn serial(&self) -> Option<String> {
+        self.node.prop_str("inquiry-serial-no").map(|s| s.trim().to_string())
+    }
+
+    /**
+     * The SAS address of the enclosure device; e.g., "5003048001a3b4fd".
+     */
+    pub fn target_port(&self) -> Result<Option<String>> {
+        Ok(sas_ports(&self.node)?.target.into_iter().next())
+    }
+}
+
+impl std::fmt::Debug for Enclosure<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Enclosure")
+            .field("devfs_path", &self.node.devfs_path().ok())
+            .field("vendor", &self.vendor())
+            .field("product", &self.product())
+            .finish()
+    }
+}
+
+/**
+ * The SES enclosures in a snapshot, each along with the disks found behind it,
+ * as returned by DevInfo::enclosure_map().
+ */
+pub struct EnclosureMap<'a> {
+    entries: Vec<(Enclosure<'a>, Vec<EnclosureBay<'a>>)>,
+}
+
+impl<'a> EnclosureMap<'a> {
+    /**
+     * Pair each of the disks with the enclosure it is behind, if any.
+     */
+    pub fn new(
+        enclosures: Vec<Enclosure<'a>>,
+        disks: Vec<Disk<'a>>,
+    ) -> Result<Self> {
+        let ports = enclosures
+            .iter()
+            .map(|e| sas_ports(&e.node))
+            .collect::<Result<Vec<_>>>()?;
+        let mut bays: Vec<Vec<EnclosureBay>> =
+            enclosures.iter().map(|_| Vec::new()).collect();
+
+        for disk in disks {
+            let theirs = sas_ports(disk.node())?;
+            if theirs.attached.is_empty() {
+                continue;
+            }
+            let find = |f: fn(&SasPorts) -> &[String]| {
+                let mut found = ports.iter().enumerate().filter(|(_, p)| {
+                    theirs.attached.iter().any(|a| f(p).contains(a))
+                });
+                match (found.next(), found.next()) {
+                    (Some((i, _)), None) => Some(i),
+                    _ => None,
+                }
+            };
+
+            /*
+             * Prefer an enclosure presented by the expander to which the
+             * disk is attached, and otherwise one attached to the same
+             * expander as the disk.
+             */
+            let Some(i) = find(|p| &p.target).or_else(|| find(|p| &p.attached))
+            else {
+                continue;
+            };
+
+            let n = disk.node();
+            let prop_u32 =
+                |name: &str| n.prop_i32(name).and_then(|v| v.try_into().ok());
+            bays[i].push(EnclosureBay {
+                enclosure: ports[i].target.first().cloned(),
+                slot: ENCLOSURE_SLOT_PROPS.iter().find_map(|p| prop_u32(p)),
+                phy: prop_u32(SCSI_ADDR_PROP_PHY),
+                disk,
+            });
+        }
+
+        for b in bays.iter_mut() {
+            b.sort_by_key(|b| (b.slot, b.phy));
+        }
+        Ok(EnclosureMap { entries: enclosures.into_iter().zip(bays).collect() })
+    }
+
+    /**
+     * Each enclosure, along with the disks behind it ordered by slot (or
+     * PHY, if the slot is not known).
+     */
+    pub fn iter(
+        &self,
+    ) -> impl Iterator<Item = (&Enclosure<'a>, &[EnclosureBay<'a>])> {
+        self.entries.iter().map(|(e, b)| (e, b.as_slice()))
+    }
+
+    /**
+     * Find the enclosure that a disk is behind, if any, along with the bay
+     * it occupies.
+     */
+    pub fn bay_of(
+        &self,
+        disk: &Disk,
+    ) -> Option<(&Enclosure<'a>, &EnclosureBay<'a>)> {
+        self.entries.iter().find_map(|(e, bays)| {
+            let b =
+                bays.iter().find(|b| b.disk.node().node == disk.node().node)?;
+            Some((e, b))
+        })
+    }
+}
+
+/**
+ * A disk found behind an enclosure, along with what is known of the bay it
+ * occupies.
+ */
+pub struct EnclosureBay<'a> {
+    /**
+     * The SAS address of the enclosure device, if it has one.
+     */
+    pub enclosure: Option<String>,
+    /**
+     * The number of the enclosure slot (or bay) that holds the disk, if the
+     * HBA driver publishes one.
+     */
+    pub slot: Option<u32>,
+    /**
+     * The number of the expander PHY to which the disk is attached, if the
+     * HBA driver reports it.  Each PHY is usually wired to a fixed bay, but
+     * the PHY number is not the bay number, and the mapping between them is
+     * particular to the enclosure.
+     */
+    pub phy: Option<u32>,
+    pub disk: Disk<'a>,
+}
+
+impl EnclosureBay<'_> {
+    /**
+     * An identifier for the bay that is stable as long as the disk remains
+     * in the same bay of the same enclosure; e.g., "5003048001a3b4fd:slot12"
+     * if the slot is known, or "5003048001a3b4fd:phy12" if only the PHY is.
+     * Returns None if neither is known, or if the enclosure device has no
+     * SAS address with which to tell it apart from other enclosures.
+     */
+    pub fn bay_id(&self) -> Option<String> {
+        let enclosure = self.enclosure.as_deref()?;
+        match (self.slot, self.phy) {
+            (Some(slot), _) => Some(format!("{}:slot{}", enclosure, slot)),
+            (None, Some(phy)) => Some(format!("{}:phy{}", enclosure, phy)),
+            (None, None) => None,
+        }
+    }
+}
+
+impl std::fmt::Debug for EnclosureBay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EnclosureBay")
+            .field("enclosure", &self.enclosure)
+            .field("slot", &self.slot)
+            .field("phy", &self.phy)
+            .field("disk", &self.disk)
+            .finish()
+    }
+}
@@ -0,0 +1,402 @@
+/*
+ * Copyright 2024 Oxide Computer Company
+ */
+
+/*
+ * Subscription to device events (EC_dev_add, EC_dev_remove, and
+ * EC_dev_status) through sysevent(3SYSEVENT).  libsysevent does not pass any
+ * argument through to the event handler, so a single handle is shared by
+ * every subscriber in the process; it is bound when the first subscriber
+ * arrives and unbound when the last one goes away.
+ */
+
+use crate::sys::*;
+use anyhow::{bail, Result};
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/*
+ * Event class and attribute names, from <sys/sysevent/eventdefs.h> and
+ * <sys/sysevent/dev.h>.
+ */
+const EC_DEV_ADD: &str = "EC_dev_add";
+const EC_DEV_REMOVE: &str = "EC_dev_remove";
+const EC_DEV_STATUS: &str = "EC_dev_status";
+const EC_SUB_ALL: &str = "all";
+const DEV_PHYS_PATH: &str = "phys_path";
+const DEV_NAME: &str = "dev_name";
+const DEV_DRIVER_NAME: &str = "driver_name";
+const DEV_INSTANCE: &str = "instance";
+
+const EVENT_CLASSES: [&str; 3] = [EC_DEV_ADD, EC_DEV_REMOVE, EC_DEV_STATUS];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DeviceEventClass {
+    /**
+     * A device was added to the system (EC_dev_add).
+     */
+    Add,
+    /**
+     * A device was removed from the system (EC_dev_remove).
+     */
+    Remove,
+    /**
+     * The status of a device changed (EC_dev_status); e.g., a disk was
+     * resized.
+     */
+    Status,
+}
+
+impl DeviceEventClass {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            EC_DEV_ADD => Some(DeviceEventClass::Add),
+            EC_DEV_REMOVE => Some(DeviceEventClass::Remove),
+            EC_DEV_STATUS => Some(DeviceEventClass::Status),
+            _ => None,
+        }
+    }
+}
+
+/**
+ * A device event, as delivered by DeviceEvents.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceEvent {
+    pub class: DeviceEventClass,
+    /**
+     * The event subclass, which describes the kind of device; e.g., "disk",
+     * "network", or "lofi".
+     */
+    pub subclass: String,
+    /**
+     * The devfs path of the device; e.g., "/pci@0,0/pci1022,1483@1,1/...".
+     */
+    pub devfs_path: Option<String>,
+    /**
+     * The public name of the device, where one exists; e.g., "/dev/dsk/c1t0d0"
+     * for a disk.
+     */
+    pub dev_name: Option<String>,
+    pub driver: Option<String>,
+    pub instance: Option<i32>,
+}
+
+impl DeviceEvent {
+    fn from_raw(ev: *mut sysevent_t) -> Option<DeviceEvent> {
+        let class = cstr_string(unsafe { sysevent_get_class_name(ev) })?;
+        let class = DeviceEventClass::from_name(&class)?;
+        let subclass = cstr_string(unsafe { sysevent_get_subclass_name(ev) })
+            .unwrap_or_default();
+
+        let mut out = DeviceEvent {
+            class,
+            subclass,
+            devfs_path: None,
+            dev_name: None,
+            driver: None,
+            instance: None,
+        };
+
+        let mut nvl: *mut nvlist_t = std::ptr::null_mut();
+        if unsafe { sysevent_get_attr_list(ev, &mut nvl) } == 0 {
+            out.devfs_path = nvlist_string(nvl, DEV_PHYS_PATH);
+            out.dev_name = nvlist_string(nvl, DEV_NAME);
+            out.driver = nvlist_string(nvl, DEV_DRIVER_NAME);
+            out.instance = nvlist_int32(nvl, DEV_INSTANCE);
+            unsafe { nvlist_free(nvl) };
+        }
+
+        Some(out)
+    }
+}
+
+fn cstr_string(p: *const c_char) -> Option<String> {
+    if p.is_null() {
+        None
+    } else {
+        Some(unsafe { CStr::from_ptr(p) }.to_string_lossy().to_string())
+    }
+}
+
+fn nvlist_string(nvl: *mut nvlist_t, name: &str) -> Option<String> {
+    let name = CString::new(name).ok()?;
+    let mut val: *mut c_char = std::ptr::null_mut();
+    if unsafe { nvlist_lookup_string(nvl, name.as_ptr(), &mut val) } != 0 {
+        return None;
+    }
+    cstr_string(val)
+}
+
+fn nvlist_int32(nvl: *mut nvlist_t, name: &str) -> Option<i32> {
+    let name = CString::new(name).ok()?;
+    let mut val = 0;
+    if unsafe { nvlist_lookup_int32(nvl, name.as_ptr(), &mut val) } != 0 {
+        return None;
+    }
+    Some(val)
+}
+
+/*
+ * Deliver an event to a subscriber, returning false if the subscriber has
+ * gone away.
+ */
+type EventSink = Box<dyn Fn(&DeviceEvent) -> bool + Send>;
+
+struct EventState {
+    handle: *mut sysevent_handle_t,
+    next_id: u64,
+    subscribers: Vec<(u64, EventSink)>,
+}
+
+/*
+ * The handle is only used while holding the lock.
+ */
+unsafe impl Send for EventState {}
+
+static EVENT_STATE: Mutex<EventState> = Mutex::new(EventState {
+    handle: std::ptr::null_mut(),
+    next_id: 0,
+    subscribers: Vec::new(),
+});
+
+/*
+ * Called by libsysevent, on a thread of its own, for each event.
+ */
+unsafe extern "C" fn event_handler(ev: *mut sysevent_t) {
+    /*
+     * We must not unwind into libsysevent.  There is nobody to report a
+     * panic to, so the event is dropped.
+     */
+    let _ = std::panic::catch_unwind(|| {
+        let Some(event) = DeviceEvent::from_raw(ev) else {
+            return;
+        };
+
+        let Ok(mut st) = EVENT_STATE.lock() else {
+            return;
+        };
+        st.subscribers.retain(|(_, sink)| sink(&event));
+    });
+}
+
+fn unbind(handle: *mut sysevent_handle_t) {
+    for class in EVENT_CLASSES {
+        let class = CString::new(class).unwrap();
+        unsafe { sysevent_unsubscribe_event(handle, class.as_ptr()) };
+    }
+    unsafe { sysevent_unbind_handle(handle) };
+}
+
+fn bind() -> Result<*mut sysevent_handle_t> {
+    let handle = unsafe { sysevent_bind_handle(event_handler) };
+    if handle.is_null() {
+        let e = std::io::Error::last_os_error();
+        bail!("sysevent_bind_handle: {}", e);
+    }
+
+    let all = CString::new(EC_SUB_ALL).unwrap();
+    for class in EVENT_CLASSES {
+        let cclass = CString::new(class).unwrap();
+        let mut subclasses = [all.as_ptr()];
+        let r = unsafe {
+            sysevent_subscribe_event(
+                handle,
+                cclass.as_ptr(),
+                subclasses.as_mut_ptr(),
+                1,
+            )
+        };
+        if r != 0 {
+            let e = std::io::Error::last_os_error();
+            unbind(handle);
+            bail!("sysevent_subscribe_event({}): {}", class, e);
+        }
+    }
+
+    Ok(handle)
+}
+
+fn subscribe(sink: EventSink) -> Result<u64> {
+    check_platform()?;
+
+    /*
+     * The handler takes the lock, and binding (or unbinding, on failure) may
+     * have to wait for it, so the handle is bound without holding the lock.
+     * If another subscriber raced with us, use theirs.
+     */
+    let mut handle: *mut sysevent_handle_t = std::ptr::null_mut();
+    let id = loop {
+        let mut st = EVENT_STATE.lock().unwrap();
+        if st.handle.is_null() && !handle.is_null() {
+            st.handle = std::mem::replace(&mut handle, std::ptr::null_mut());
+        }
+        if !st.handle.is_null() {
+            let id = st.next_id;
+            st.next_id += 1;
+            st.subscribers.push((id, sink));
+            break id;
+        }
+        drop(st);
+
+        handle = bind()?;
+    };
+
+    if !handle.is_null() {
+        unbind(handle);
+    }
+
+    Ok(id)
+}
+
+fn unsubscribe(id: u64) {
+    let handle = {
+        let Ok(mut st) = EVENT_STATE.lock() else {
+            return;
+        };
+        st.subscribers.retain(|(sid, _)| *sid != id);
+        if !st.subscribers.is_empty() {
+            return;
+        }
+        std::mem::replace(&mut st.handle, std::ptr::null_mut())
+    };
+
+    /*
+     * The handler takes the lock, so the handle must be unbound without
+     * holding it.
+     */
+    if !handle.is_null() {
+        unbind(handle);
+    }
+}
+
+/**
+ * A subscription to device add, remove, and status events.  Events that occur
+ * after the subscription is created are queued until received.  Subscribing
+ * requires the privileges needed by sysevent_bind_handle(3SYSEVENT); i.e.,
+ * generally, root.
+ */
+pub struct DeviceEvents {
+    id: u64,
+    rx: Receiver<DeviceEvent>,
+}
+
+impl DeviceEvents {
+    pub fn subscribe() -> Result<Self> {
+        let (tx, rx) = channel();
+        let id = subscribe(Box::new(move |ev| tx.send(ev.clone()).is_ok()))?;
+        Ok(DeviceEvents { id, rx })
+    }
+
+    /**
+     * Wait for the next event.
+     */
+    pub fn recv(&self) -> Result<DeviceEvent> {
+        match self.rx.recv() {
+            Ok(ev) => Ok(ev),
+            Err(_) => bail!("device event subscription is no longer active"),
+        }
+    }
+
+    /**
+     * Wait for the next event, for at most the given time.  Returns None if
+     * no event arrived in that time.
+     */
+    pub fn recv_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<Option<DeviceEvent>> {
+        match self.rx.recv_timeout(timeout) {
+            Ok(ev) => Ok(Some(ev)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => {
+                bail!("device event subscription is no longer active")
+            }
+        }
+    }
+
+    /**
+     * Return the next event if one is already queued, without waiting.
+     */
+    pub fn try_recv(&self) -> Option<DeviceEvent> {
+        self.rx.try_recv().ok()
+    }
+}
+
+impl Iterator for DeviceEvents {
+    type Item = DeviceEvent;
+
+    fn next(&mut self) -> Option<DeviceEvent> {
+        self.rx.recv().ok()
+    }
+}
+
+impl Drop for DeviceEvents {
+    fn drop(&mut self) {
+        unsubscribe(self.id);
+    }
+}
+
+impl std::fmt::Debug for DeviceEvents {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeviceEvents").field("id", &self.id).finish()
+    }
+}
+
+/**
+ * A subscription to device events for use in async code, as a Stream of
+ * DeviceEvent.  As with DeviceEvents, events are queued from the time the
+ * subscription is created.
+ */
+#[cfg(feature = "tokio")]
+pub struct DeviceEventStream {
+    id: u64,
+    rx: tokio::sync::mpsc::UnboundedReceiver<DeviceEvent>,
+}
+
+#[cfg(feature = "tokio")]
+impl DeviceEventStream {
+    pub fn subscribe() -> Result<Self> {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let id = subscribe(Box::new(move |ev| tx.send(ev.clone()).is_ok()))?;
+        Ok(DeviceEventStream { id, rx })
+    }
+
+    /**
+     * Wait for the next event.
+     */
+    pub async fn recv(&mut self) -> Option<DeviceEvent> {
+        self.rx.recv().await
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl futures_core::Stream for DeviceEventStream {
+    type Item = DeviceEvent;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<DeviceEvent>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Drop for DeviceEventStream {
+    fn drop(&mut self) {
+        unsubscribe(self.id);
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl std::fmt::Debug for DeviceEventStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeviceEventStream").field("id", &self.id).finish()
+    }
+}
@@ -0,0 +1,336 @@
+/*
+ * Copyright 2024 Oxide Computer Company
+ */
+
+use crate::sys::*;
+use crate::{DevInfo, Node, SnapshotFlags};
+use anyhow::{bail, Result};
+use num_enum::TryFromPrimitive;
+use std::ffi::CStr;
+use std::time::{Duration, Instant, SystemTime};
+
+/*
+ * How often to take a new snapshot while waiting for a hotplug connection to
+ * change state.
+ */
+const HOTPLUG_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/**
+ * The state of a hotplug connector or port, from <sys/ddi_hp.h>.  Physical
+ * connectors move between the EMPTY, PRESENT, POWERED, and ENABLED states,
+ * while virtual ports use the PORT_EMPTY, PORT_PRESENT, OFFLINE, ATTACHED,
+ * MAINTENANCE, and ONLINE states.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, TryFromPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(i32)]
+#[non_exhaustive]
+pub enum HotplugState {
+    Empty = 0x1000,
+    Present = 0x2000,
+    Powered = 0x3000,
+    Enabled = 0x4000,
+    PortEmpty = 0x5000,
+    PortPresent = 0x6000,
+    Offline = 0x7000,
+    Attached = 0x8000,
+    Maintenance = 0x9000,
+    Online = 0xa000,
+}
+
+impl std::fmt::Display for HotplugState {
+    /*
+     * These are the names used by hotplug(8).
+     */
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            HotplugState::Empty => "EMPTY",
+            HotplugState::Present => "PRESENT",
+            HotplugState::Powered => "POWERED",
+            HotplugState::Enabled => "ENABLED",
+            HotplugState::PortEmpty => "PORT-EMPTY",
+            HotplugState::PortPresent => "PORT-PRESENT",
+            HotplugState::Offline => "OFFLINE",
+            HotplugState::Attached => "ATTACHED",
+            HotplugState::Maintenance => "MAINTENANCE",
+            HotplugState::Online => "ONLINE",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/**
+ * The type of a hotplug connection, from <sys/ddi_hp.h>.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, TryFromPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(i32)]
+#[non_exhaustive]
+pub enum HotplugType {
+    /**
+     * A virtual port, through which a child node is attached to a connector.
+     */
+    VirtualPort = 0x1,
+    Pci = 0x2,
+    PciExpress = 0x3,
+}
+
+impl DevInfo {
+    /**
+     * Take a snapshot of the whole tree that includes hotplug connection
+     * information, as is required for Node::hotplug_connectors().
+     */
+    pub fn new_with_hotplug() -> Result<Self> {
+        DevInfo::builder().with(SnapshotFlags::HP).build()
+    }
+
+    /**
+     * Collect every hotplug connector and port in the snapshot, along with
+     * the node (e.g., a PCIe bridge) to which each belongs.
+     */
+    pub fn hotplug_connectors(
+        &self,
+    ) -> Result<Vec<(Node<'_>, HotplugConnector<'_>)>> {
+        let mut out = Vec::new();
+        for n in self.walk_node() {
+            let n = n?;
+            for hp in n.hotplug_connectors() {
+                out.push((n.clone(), hp?));
+            }
+        }
+        Ok(out)
+    }
+
+    /**
+     * Find a hotplug connection by either its logical attachment point ID
+     * (e.g., "pcie2") or its physical attachment point ID (e.g.,
+     * "/devices/pci@0,0/pci1022,1483@1,1:pcie2"), as used by cfgadm(8).
+     */
+    pub fn hotplug_connector_by_ap_id(
+        &self,
+        ap_id: &str,
+    ) -> Result<Option<HotplugConnector<'_>>> {
+        let physical = ap_id.starts_with('/');
+        for (_, hp) in self.hotplug_connectors()? {
+            let id = if physical { hp.ap_id_physical()? } else { hp.ap_id() };
+            if id == ap_id {
+                return Ok(Some(hp));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<'a> Node<'a> {
+    /**
+     * Walk the hotplug connectors and ports of this node.  The snapshot must
+     * have been taken with SnapshotFlags::HP; otherwise, no connectors are
+     * returned.
+     */
+    pub fn hotplug_connectors(&self) -> HotplugConnectorWalk<'a> {
+        HotplugConnectorWalk {
+            parent: self.parent,
+            node: self.node,
+            hp: DI_HP_NIL,
+            fin: false,
+        }
+    }
+}
+
+pub struct HotplugConnectorWalk<'p> {
+    parent: &'p DevInfo,
+    node: *mut di_node_t,
+    hp: *mut di_hp_t,
+    fin: bool,
+}
+
+impl<'a> Iterator for HotplugConnectorWalk<'a> {
+    type Item = Result<HotplugConnector<'a>>;
+
+    fn next(&mut self) -> Option<Result<HotplugConnector<'a>>> {
+        if self.fin {
+            return None;
+        }
+
+        self.hp = unsafe { di_hp_next(self.node, self.hp) };
+        if self.hp == DI_HP_NIL {
+            self.fin = true;
+            return None;
+        }
+
+        Some(Ok(HotplugConnector {
+            parent: self.parent,
+            node: self.node,
+            hp: self.hp,
+        }))
+    }
+}
+
+pub struct HotplugConnector<'p> {
+    parent: &'p DevInfo,
+    node: *mut di_node_t,
+    hp: *mut di_hp_t,
+}
+
+impl<'a> HotplugConnector<'a> {
+    /**
+     * The name of the connector or port; e.g., "pcie2".
+     */
+    pub fn name(&self) -> String {
+        unsafe { CStr::from_ptr(di_hp_name(self.hp)) }
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /**
+     * The node to which this connection belongs; e.g., the PCIe bridge or
+     * root port for a slot.
+     */
+    pub fn node(&self) -> Node<'a> {
+        Node { parent: self.parent, node: self.node }
+    }
+
+    /**
+     * The logical attachment point ID for this connection, as displayed by
+     * cfgadm(8) and accepted by cfgadm(8) and hotplug(8); e.g., "pcie2".
+     */
+    pub fn ap_id(&self) -> String {
+        self.name()
+    }
+
+    /**
+     * The physical attachment point ID for this connection, made up of the
+     * /devices path of the node to which it belongs and the connection name;
+     * e.g., "/devices/pci@0,0/pci1022,1483@1,1:pcie2".
+     */
+    pub fn ap_id_physical(&self) -> Result<String> {
+        let path = self.node().devfs_path_full()?;
+        Ok(format!("{}:{}", path.display(), self.name()))
+    }
+
+    /**
+     * The connection number, which is unique among the connectors and ports
+     * of a node.
+     */
+    pub fn connection(&self) -> i32 {
+        unsafe { di_hp_connection(self.hp) }
+    }
+
+    /**
+     * For a virtual port, the connection number of the connector on which
+     * it depends.
+     */
+    pub fn depends_on(&self) -> Option<i32> {
+        let n = unsafe { di_hp_depends_on(self.hp) };
+        (n >= 0).then_some(n)
+    }
+
+    /**
+     * The current state, or None if the state is not one known to this
+     * crate.
+     */
+    pub fn state(&self) -> Option<HotplugState> {
+        HotplugState::try_from(unsafe { di_hp_state(self.hp) }).ok()
+    }
+
+    pub fn state_raw(&self) -> i32 {
+        unsafe { di_hp_state(self.hp) }
+    }
+
+    /**
+     * The type of connection, or None if the type is not one known to this
+     * crate.
+     */
+    pub fn connector_type(&self) -> Option<HotplugType> {
+        HotplugType::try_from(unsafe { di_hp_type(self.hp) }).ok()
+    }
+
+    /**
+     * A description of the type of connection; e.g., "PCI Express slot".
+     */
+    pub fn description(&self) -> Option<String> {
+        let p = unsafe { di_hp_description(self.hp) };
+        if p.is_null() {
+            None
+        } else {
+            Some(unsafe { CStr::from_ptr(p) }.to_string_lossy().to_string())
+        }
+    }
+
+    /**
+     * The node that occupies this connection, if any; e.g., the NVMe
+     * controller in a PCIe slot.  Empty connections have no occupant.
+     */
+    pub fn occupant(&self) -> Option<Node<'a>> {
+        let node = unsafe { di_hp_child(self.hp) };
+        if node == DI_NODE_NIL {
+            None
+        } else {
+            Some(Node { parent: self.parent, node })
+        }
+    }
+
+    /**
+     * The time at which the state of the connection last changed.
+     */
+    pub fn last_change(&self) -> Option<SystemTime> {
+        let t = unsafe { di_hp_last_change(self.hp) };
+        let t = u64::try_from(t).ok().filter(|t| *t > 0)?;
+        SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(t))
+    }
+}
+
+impl std::fmt::Debug for HotplugConnector<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HotplugConnector")
+            .field("name", &self.name())
+            .field("connection", &self.connection())
+            .field("state", &self.state())
+            .field("type", &self.connector_type())
+            .finish()
+    }
+}
+
+/**
+ * Wait for the hotplug connection with the given attachment point ID (see
+ * DevInfo::hotplug_connector_by_ap_id()) to reach the desired state; e.g.,
+ * HotplugState::Enabled after a device is inserted into a slot.  A fresh
+ * snapshot is taken periodically until the state is reached or the timeout
+ * expires.  The connection need not exist when the wait begins.
+ */
+pub fn wait_for_hotplug_state(
+    ap_id: &str,
+    state: HotplugState,
+    timeout: Duration,
+) -> Result<()> {
+    let start = Instant::now();
+
+    loop {
+        let di = DevInfo::new_with_hotplug()?;
+        let current =
+            di.hotplug_connector_by_ap_id(ap_id)?.map(|hp| hp.state_raw());
+        if current == Some(state as i32) {
+            return Ok(());
+        }
+
+        if start.elapsed() >= timeout {
+            let desc = match current {
+                Some(s) => HotplugState::try_from(s)
+                    .map(|s| s.to_string())
+                    .unwrap_or_else(|_| format!("{:#x}", s)),
+                None => "not found".to_string(),
+            };
+            bail!(
+                "{} did not reach state {} within {:?} (currently {})",
+                ap_id,
+                state,
+                timeout,
+                desc,
+            );
+        }
+
+        std::thread::sleep(
+            HOTPLUG_POLL_INTERVAL.min(timeout.saturating_sub(start.elapsed())),
+        );
+    }
+}
@@ -0,0 +1,291 @@
+/*
+ * Copyright 2024 Oxide Computer Company
+ */
+
+use crate::sys::*;
+use crate::{DevInfo, Node, SnapshotFlags, SpecType};
+use anyhow::Result;
+use libc::dev_t;
+use std::ffi::CStr;
+use std::os::raw::c_uint;
+
+/**
+ * Which end of a layered link a node or lnode is on.  A layered driver (e.g.,
+ * lofi(4D) or zfs) that opens a device is the source of the link, and the
+ * device it opens is the target.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LinkEndpoint {
+    Source,
+    Target,
+}
+
+impl LinkEndpoint {
+    fn to_raw(self) -> c_uint {
+        match self {
+            LinkEndpoint::Source => DI_LINK_SRC,
+            LinkEndpoint::Target => DI_LINK_TGT,
+        }
+    }
+}
+
+impl DevInfo {
+    /**
+     * Take a snapshot of the whole tree that includes device layering
+     * information, as is required for Node::lnodes() and
+     * Node::layered_links().
+     */
+    pub fn new_with_layering() -> Result<Self> {
+        DevInfo::builder().with(SnapshotFlags::LYR).build()
+    }
+}
+
+impl<'a> Node<'a> {
+    /**
+     * Walk the layering nodes (lnodes) associated with this node.  An lnode
+     * represents a particular endpoint (i.e., a device, or a layered driver
+     * that has opened one) in the layering graph.  The snapshot must have
+     * been taken with SnapshotFlags::LYR; otherwise, no lnodes are returned.
+     */
+    pub fn lnodes(&self) -> LnodeWalk<'a> {
+        LnodeWalk {
+            parent: self.parent,
+            node: self.node,
+            lnode: DI_LNODE_NIL,
+            fin: false,
+        }
+    }
+
+    /**
+     * Walk the layered links for which this node is the given endpoint.  For
+     * example, the links for which a disk is the target describe the layered
+     * consumers (e.g., zfs) that have it open.
+     */
+    pub fn layered_links(&self, endpoint: LinkEndpoint) -> LayeredLinkWalk<'a> {
+        LayeredLinkWalk {
+            parent: self.parent,
+            node: self.node,
+            endpoint,
+            link: DI_LYR_LINK_NIL,
+            fin: false,
+        }
+    }
+
+    /**
+     * List the layered consumers that currently have this device open; e.g.,
+     * zfs for a pool vdev, or lofi(4D) for a file-backed device.  This is the
+     * source of each layered link that targets this node, with each consumer
+     * listed once.  It answers, from snapshot data alone, why a device may be
+     * busy; note that opens from user processes are not layered and are not
+     * included.
+     */
+    pub fn consumers(&self) -> Result<Vec<Lnode<'a>>> {
+        let mut out: Vec<Lnode<'a>> = Vec::new();
+        for l in self.layered_links(LinkEndpoint::Target) {
+            let Some(src) = l?.lnode(LinkEndpoint::Source) else {
+                continue;
+            };
+
+            if !out.iter().any(|o| o.lnode == src.lnode) {
+                out.push(src);
+            }
+        }
+        Ok(out)
+    }
+}
+
+pub struct LnodeWalk<'p> {
+    parent: &'p DevInfo,
+    node: *mut di_node_t,
+    lnode: *mut di_lnode_t,
+    fin: bool,
+}
+
+impl<'a> Iterator for LnodeWalk<'a> {
+    type Item = Result<Lnode<'a>>;
+
+    fn next(&mut self) -> Option<Result<Lnode<'a>>> {
+        if self.fin {
+            return None;
+        }
+
+        self.lnode = unsafe { di_lnode_next(self.node, self.lnode) };
+        if self.lnode == DI_LNODE_NIL {
+            self.fin = true;
+            return None;
+        }
+
+        Some(Ok(Lnode { parent: self.parent, lnode: self.lnode }))
+    }
+}
+
+pub struct Lnode<'p> {
+    parent: &'p DevInfo,
+    lnode: *mut di_lnode_t,
+}
+
+impl<'a> Lnode<'a> {
+    /**
+     * The name of the driver associated with this lnode; e.g., "zfs".
+     */
+    pub fn name(&self) -> String {
+        unsafe { CStr::from_ptr(di_lnode_name(self.lnode)) }
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /**
+     * The device number of this lnode, if it refers to a particular device
+     * rather than just a driver.
+     */
+    pub fn devt(&self) -> Option<dev_t> {
+        let mut devt: dev_t = 0;
+        if unsafe { di_lnode_devt(self.lnode, &mut devt) } == 0 {
+            Some(devt)
+        } else {
+            None
+        }
+    }
+
+    /**
+     * The device node with which this lnode is associated, if it is present
+     * in the snapshot.
+     */
+    pub fn node(&self) -> Option<Node<'a>> {
+        let node = unsafe { di_lnode_devinfo(self.lnode) };
+        (node != DI_NODE_NIL).then_some(Node { parent: self.parent, node })
+    }
+
+    /**
+     * Walk the layered links for which this lnode is the given endpoint.
+     */
+    pub fn links(&self, endpoint: LinkEndpoint) -> LnodeLinkWalk<'a> {
+        LnodeLinkWalk {
+            parent: self.parent,
+            lnode: self.lnode,
+            endpoint,
+            link: DI_LYR_LINK_NIL,
+            fin: false,
+        }
+    }
+}
+
+impl std::fmt::Debug for Lnode<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Lnode")
+            .field("name", &self.name())
+            .field("devt", &self.devt())
+            .finish()
+    }
+}
+
+pub struct LayeredLinkWalk<'p> {
+    parent: &'p DevInfo,
+    node: *mut di_node_t,
+    endpoint: LinkEndpoint,
+    link: *mut di_link_t,
+    fin: bool,
+}
+
+impl<'a> Iterator for LayeredLinkWalk<'a> {
+    type Item = Result<LayeredLink<'a>>;
+
+    fn next(&mut self) -> Option<Result<LayeredLink<'a>>> {
+        if self.fin {
+            return None;
+        }
+
+        self.link = unsafe {
+            di_link_next_by_node(self.node, self.link, self.endpoint.to_raw())
+        };
+        if self.link == DI_LYR_LINK_NIL {
+            self.fin = true;
+            return None;
+        }
+
+        Some(Ok(LayeredLink { parent: self.parent, link: self.link }))
+    }
+}
+
+pub struct LnodeLinkWalk<'p> {
+    parent: &'p DevInfo,
+    lnode: *mut di_lnode_t,
+    endpoint: LinkEndpoint,
+    link: *mut di_link_t,
+    fin: bool,
+}
+
+impl<'a> Iterator for LnodeLinkWalk<'a> {
+    type Item = Result<LayeredLink<'a>>;
+
+    fn next(&mut self) -> Option<Result<LayeredLink<'a>>> {
+        if self.fin {
+            return None;
+        }
+
+        self.link = unsafe {
+            di_link_next_by_lnode(self.lnode, self.link, self.endpoint.to_raw())
+        };
+        if self.link == DI_LYR_LINK_NIL {
+            self.fin = true;
+            return None;
+        }
+
+        Some(Ok(LayeredLink { parent: self.parent, link: self.link }))
+    }
+}
+
+/**
+ * A link in the layering graph, from a layered driver (the source) to a
+ * device it has open (the target).
+ */
+pub struct LayeredLink<'p> {
+    parent: &'p DevInfo,
+    link: *mut di_link_t,
+}
+
+impl<'a> LayeredLink<'a> {
+    /**
+     * The lnode at the given end of this link.
+     */
+    pub fn lnode(&self, endpoint: LinkEndpoint) -> Option<Lnode<'a>> {
+        let lnode = unsafe { di_link_to_lnode(self.link, endpoint.to_raw()) };
+        (lnode != DI_LNODE_NIL).then_some(Lnode { parent: self.parent, lnode })
+    }
+
+    /**
+     * The lnode of the layered driver that holds the target open.
+     */
+    pub fn source(&self) -> Option<Lnode<'a>> {
+        self.lnode(LinkEndpoint::Source)
+    }
+
+    /**
+     * The lnode of the device that is held open.
+     */
+    pub fn target(&self) -> Option<Lnode<'a>> {
+        self.lnode(LinkEndpoint::Target)
+    }
+
+    /**
+     * Whether the target was opened as a character or a block device, or
+     * None if the type is not one known to this crate.
+     */
+    pub fn spec_type(&self) -> Option<SpecType> {
+        match unsafe { di_link_spectype(self.link) as libc::mode_t } {
+            libc::S_IFCHR => Some(SpecType::Char),
+            libc::S_IFBLK => Some(SpecType::Block),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Debug for LayeredLink<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LayeredLink")
+            .field("source", &self.source())
+            .field("target", &self.target())
+            .field("spec_type", &self.spec_type())
+            .finish()
+    }
+}
@@ -3,24 +3,113 @@
  */
 
 use anyhow::{bail, Result};
-use libc::{___errno, c_void, ENXIO};
-use libdevinfo_sys::*;
+use libc::{c_void, dev_t, EAGAIN, EBUSY, ENXIO};
 use num_enum::TryFromPrimitive;
-use std::collections::BTreeMap;
+use std::collections::{btree_map, BTreeMap, BTreeSet, HashMap, VecDeque};
 use std::convert::TryFrom;
-use std::ffi::{CStr, CString, OsStr};
+use std::ffi::{CStr, CString, OsStr, OsString};
 use std::iter::Iterator;
-use std::os::raw::{c_char, c_int, c_uchar};
+use std::ops::ControlFlow;
+use std::os::raw::{c_char, c_int, c_uchar, c_uint};
 use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+mod sys;
+use sys::*;
+
+mod devfs;
+pub use devfs::{DevfsPath, DevfsPathComponent};
+
+mod diskname;
+pub use diskname::DiskName;
+
+pub mod disk;
+pub use disk::{
+    Disk, DiskController, DiskId, DiskIdKind, DiskInfo, DiskMinor,
+    DiskMinorKind, DiskTransport, DiskType,
+};
+
+pub mod nvme;
+pub use nvme::{NvmeController, NvmeInfo, NvmeNamespace, NvmeNamespaceInfo};
+
+pub mod enclosure;
+pub use enclosure::{Enclosure, EnclosureBay, EnclosureMap};
+
+pub mod nodetype;
+pub use nodetype::{MinorNodeFamily, MinorNodeType};
+
+mod snapshot;
+pub use snapshot::{DevInfoBuilder, SnapshotFlags};
+
+mod owned;
+pub use owned::{
+    OwnedDevTree, OwnedMinor, OwnedNode, OwnedNodeWalk, OwnedProperty,
+};
+
+pub mod diff;
+pub use diff::SnapshotDiff;
+
+mod watch;
+pub use watch::{WatchUpdate, Watcher, WatcherBuilder};
+
+mod tree;
+pub use tree::{DeviceTree, OwnedNodeRef, TreeMinor, TreeNode};
+
+mod select;
+pub use select::Selector;
+
+mod walk;
+pub use walk::{
+    EntryWalk, NodeMinorsWalk, OrderedNodeWalk, WalkAction, WalkEntry,
+    WalkOrder,
+};
+
+pub mod audit;
+pub use audit::AuditReport;
+
+mod prom;
+pub use prom::{Prom, PromProperty, PromPropertyWalk};
+
+mod path;
+pub use path::{
+    PathInfo, PathInfoWalk, PathProperty, PathPropertyWalk, PathState, PhciPath,
+};
+
+mod layered;
+pub use layered::{
+    LayeredLink, LayeredLinkWalk, LinkEndpoint, Lnode, LnodeLinkWalk, LnodeWalk,
+};
+
+mod hotplug;
+pub use hotplug::{
+    wait_for_hotplug_state, HotplugConnector, HotplugConnectorWalk,
+    HotplugState, HotplugType,
+};
+
+pub mod usb;
+pub use usb::{
+    BcdVersion, FoundUsbDevice, UsbConfigDescriptor, UsbDevice,
+    UsbDeviceDescriptor, UsbInterface, UsbInterfaceDescriptor, UsbSpeed,
+};
 
 #[cfg(feature = "private")]
 mod dim;
 #[cfg(feature = "private")]
 pub use dim::DevInstMinor;
 
+#[cfg(feature = "events")]
+pub mod events;
+#[cfg(feature = "tokio")]
+pub use events::DeviceEventStream;
+#[cfg(feature = "events")]
+pub use events::{DeviceEvent, DeviceEventClass, DeviceEvents};
+
 #[derive(Debug, Eq, PartialEq, TryFromPrimitive)]
 #[repr(i32)]
+#[non_exhaustive]
 pub enum PropType {
     Boolean = 0,
     Int32 = 1,
@@ -41,6 +130,21 @@ impl Drop for DevInfo {
     }
 }
 
+/*
+ * Once it has been taken, a snapshot is a read-only copy of the device tree
+ * that does not refer to any thread-specific state, so it may be moved to, and
+ * inspected from, other threads.  The nodes, minors, and properties within
+ * the snapshot borrow it, and are likewise safe to share.
+ */
+unsafe impl Send for DevInfo {}
+unsafe impl Sync for DevInfo {}
+unsafe impl Send for Node<'_> {}
+unsafe impl Sync for Node<'_> {}
+unsafe impl Send for Minor<'_> {}
+unsafe impl Sync for Minor<'_> {}
+unsafe impl Send for Property<'_> {}
+unsafe impl Sync for Property<'_> {}
+
 fn string_props(node: *mut di_node_t) -> BTreeMap<String, String> {
     let mut out = BTreeMap::new();
     let mut prop = DI_PROP_NIL;
@@ -69,19 +173,169 @@ fn string_props(node: *mut di_node_t) -> BTreeMap<String, String> {
     out
 }
 
+fn string_props_all(node: *mut di_node_t) -> BTreeMap<String, Vec<String>> {
+    let mut out = BTreeMap::new();
+    let mut prop = DI_PROP_NIL;
+    loop {
+        prop = unsafe { di_prop_next(node, prop) };
+        if prop == DI_PROP_NIL {
+            break;
+        }
+
+        if unsafe { di_prop_type(prop) } != DI_PROP_TYPE_STRING {
+            continue;
+        }
+
+        let mut data = std::ptr::null_mut();
+        let n = unsafe { di_prop_strings(prop, &mut data) };
+        let Some(vals) = prop_strings(n, data) else {
+            continue;
+        };
+
+        let name =
+            unsafe { CStr::from_ptr(di_prop_name(prop)) }.to_string_lossy();
+
+        out.entry(name.to_string()).or_insert_with(|| {
+            vals.iter().map(|v| v.to_string_lossy().to_string()).collect()
+        });
+    }
+    out
+}
+
+/*
+ * The di_prop_lookup_*() family of functions return the number of values in
+ * the property, with the values themselves stored in memory that belongs to
+ * the snapshot.  A boolean property has no values.
+ */
+fn prop_slice<'a, T>(n: c_int, data: *const T) -> Option<&'a [T]> {
+    match n {
+        n if n < 0 => None,
+        0 => Some(&[]),
+        n => Some(unsafe {
+            std::slice::from_raw_parts(data, n.try_into().unwrap())
+        }),
+    }
+}
+
+/*
+ * String property values are packed one after the other, each with its own
+ * NUL terminator.
+ */
+fn prop_strings<'a>(n: c_int, data: *const c_char) -> Option<Vec<&'a CStr>> {
+    if n < 0 {
+        return None;
+    }
+
+    let mut out = Vec::new();
+    let mut p = data;
+    for _ in 0..n {
+        let s = unsafe { CStr::from_ptr(p) };
+        p = unsafe { p.add(s.to_bytes_with_nul().len()) };
+        out.push(s);
+    }
+    Some(out)
+}
+
+fn lookup_ints<'a>(
+    dev: dev_t,
+    node: *mut di_node_t,
+    name: &str,
+) -> Option<&'a [i32]> {
+    let name = CString::new(name).ok()?;
+    let mut data: *mut c_int = std::ptr::null_mut();
+    let n = unsafe { di_prop_lookup_ints(dev, node, name.as_ptr(), &mut data) };
+    prop_slice(n, data)
+}
+
+fn lookup_int64s<'a>(
+    dev: dev_t,
+    node: *mut di_node_t,
+    name: &str,
+) -> Option<&'a [i64]> {
+    let name = CString::new(name).ok()?;
+    let mut data: *mut i64 = std::ptr::null_mut();
+    let n =
+        unsafe { di_prop_lookup_int64(dev, node, name.as_ptr(), &mut data) };
+    prop_slice(n, data)
+}
+
+fn lookup_strings<'a>(
+    dev: dev_t,
+    node: *mut di_node_t,
+    name: &str,
+) -> Option<Vec<&'a CStr>> {
+    let name = CString::new(name).ok()?;
+    let mut data: *mut c_char = std::ptr::null_mut();
+    let n =
+        unsafe { di_prop_lookup_strings(dev, node, name.as_ptr(), &mut data) };
+    prop_strings(n, data)
+}
+
+fn lookup_bytes<'a>(
+    dev: dev_t,
+    node: *mut di_node_t,
+    name: &str,
+) -> Option<&'a [u8]> {
+    let name = CString::new(name).ok()?;
+    let mut data: *mut c_uchar = std::ptr::null_mut();
+    let n =
+        unsafe { di_prop_lookup_bytes(dev, node, name.as_ptr(), &mut data) };
+    prop_slice(n, data)
+}
+
+fn os_string(p: *const c_char) -> OsString {
+    OsStr::from_bytes(unsafe { CStr::from_ptr(p) }.to_bytes()).to_os_string()
+}
+
+/*
+ * Copy a path returned by di_devfs_path(3DEVINFO) or
+ * di_devfs_minor_path(3DEVINFO) and release the original.
+ */
+fn take_devfs_path(p: *mut c_char) -> OsString {
+    let out = os_string(p);
+    unsafe { di_devfs_path_free(p) };
+    out
+}
+
+/*
+ * Paths returned by di_devfs_path(3DEVINFO) are relative to the mount point
+ * of the devfs file system.
+ */
+const DEVFS_ROOT: &str = "/devices";
+
+fn devfs_path_full(path: &OsStr) -> PathBuf {
+    let mut rel = path.as_bytes();
+    while let Some(r) = rel.strip_prefix(b"/") {
+        rel = r;
+    }
+    Path::new(DEVFS_ROOT).join(OsStr::from_bytes(rel))
+}
+
+/*
+ * How often to take a new snapshot while waiting for a device to appear.
+ */
+const DEVICE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/*
+ * The layout of a 64-bit dev_t, from <sys/mkdev.h>.
+ */
+const L_BITSMINOR: u32 = 32;
+const L_MAXMIN: dev_t = (1 << L_BITSMINOR) - 1;
+
+pub fn devt_major(dev: dev_t) -> u32 {
+    (dev >> L_BITSMINOR).try_into().unwrap()
+}
+
+pub fn devt_minor(dev: dev_t) -> u32 {
+    (dev & L_MAXMIN).try_into().unwrap()
+}
+
 impl DevInfo {
-    fn new_common<P: AsRef<Path>>(p: P, force_load: bool) -> Result<Self> {
+    fn new_common<P: AsRef<Path>>(p: P, flags: SnapshotFlags) -> Result<Self> {
+        check_platform()?;
         let path = CString::new(p.as_ref().as_os_str().as_bytes()).unwrap();
-        #[allow(unused_mut)]
-        let mut flags = DINFOCPYALL;
-        #[cfg(feature = "private")]
-        if force_load {
-            flags |= DINFOFORCE;
-        }
-        #[cfg(not(feature = "private"))]
-        assert!(!force_load);
 
-        let root = unsafe { di_init(path.as_ptr(), flags) };
+        let root = unsafe { di_init(path.as_ptr(), flags.to_raw()) };
         if root == DI_NODE_NIL {
             let e = std::io::Error::last_os_error();
             bail!("di_init: {}", e);
@@ -90,20 +344,287 @@ impl DevInfo {
         Ok(DevInfo { root })
     }
 
+    fn new_driver_common(driver: &str, flags: SnapshotFlags) -> Result<Self> {
+        check_platform()?;
+        let cdrv = CString::new(driver)?;
+
+        let root = unsafe { di_init_driver(cdrv.as_ptr(), flags.to_raw()) };
+        if root == DI_NODE_NIL {
+            let e = std::io::Error::last_os_error();
+            bail!("di_init_driver({}): {}", driver, e);
+        }
+
+        Ok(DevInfo { root })
+    }
+
+    pub fn builder() -> DevInfoBuilder {
+        DevInfoBuilder::new()
+    }
+
     pub fn new_path<P: AsRef<Path>>(p: P) -> Result<Self> {
-        Self::new_common(p, false)
+        Self::new_common(p, SnapshotFlags::CPYALL)
     }
 
     pub fn new() -> Result<Self> {
-        Self::new_common("/", false)
+        Self::new_common("/", SnapshotFlags::CPYALL)
+    }
+
+    /**
+     * Take a snapshot of the shape of the whole tree, without properties or
+     * minor nodes.  This is much cheaper than a full snapshot on large
+     * systems, for consumers that only need the topology.
+     */
+    pub fn new_minimal() -> Result<Self> {
+        Self::new_common("/", SnapshotFlags::SUBTREE)
+    }
+
+    /**
+     * Take a snapshot of the whole tree with minor nodes, but without
+     * properties.
+     */
+    pub fn new_with_minors() -> Result<Self> {
+        Self::new_common("/", SnapshotFlags::SUBTREE | SnapshotFlags::MINOR)
+    }
+
+    /**
+     * Take a snapshot that includes only the nodes bound to a single driver
+     * (e.g., "nvme").
+     */
+    pub fn new_for_driver(driver: &str) -> Result<Self> {
+        Self::new_driver_common(driver, SnapshotFlags::CPYALL)
     }
 
     #[cfg(feature = "private")]
     pub fn new_force_load() -> Result<Self> {
-        Self::new_common("/", true)
+        Self::new_common("/", SnapshotFlags::CPYALL | SnapshotFlags::FORCE)
+    }
+
+    /**
+     * Copy the entire snapshot into plain Rust structures that do not borrow
+     * from this handle.
+     */
+    pub fn to_owned_tree(&self) -> Result<OwnedDevTree> {
+        OwnedDevTree::from_devinfo(self)
+    }
+
+    /**
+     * Find the node with the given devfs path (e.g., "/pci@0,0/pci@1,1"),
+     * using di_lookup_node(3DEVINFO).  A leading "/devices" and any minor
+     * name are ignored.  Returns None if the node is not in the snapshot.
+     */
+    pub fn node_by_path(&self, path: &str) -> Result<Option<Node<'_>>> {
+        let path: DevfsPath = path.parse()?;
+        let cpath = CString::new(path.node_path().to_string())?;
+
+        let node = unsafe { di_lookup_node(self.root, cpath.as_ptr()) };
+        if node == DI_NODE_NIL {
+            let e = std::io::Error::last_os_error();
+            if e.raw_os_error() == Some(ENXIO) {
+                return Ok(None);
+            }
+            bail!("di_lookup_node({}): {}", path, e);
+        }
+
+        Ok(Some(Node { parent: self, node }))
+    }
+
+    /**
+     * Wait for the node with the given devfs path (e.g.,
+     * "/pci@0,0/pci1022,1483@1,1/pci144d,a801@0") to appear and have a
+     * driver attached, taking a new snapshot of that node periodically until
+     * it does or the timeout expires.  Returns a snapshot rooted at the node.
+     */
+    pub fn wait_for_path(devfs_path: &str, timeout: Duration) -> Result<Self> {
+        check_platform()?;
+        let path: DevfsPath = devfs_path.parse()?;
+        let path = path.node_path().to_string();
+        let start = Instant::now();
+
+        loop {
+            let why = match DevInfo::new_path(&path) {
+                Ok(di) => {
+                    if (Node { parent: &di, node: di.root }).is_attached() {
+                        return Ok(di);
+                    }
+                    "not attached".to_string()
+                }
+                Err(e) => e.to_string(),
+            };
+
+            if start.elapsed() >= timeout {
+                bail!("{} did not attach within {:?} ({})", path, timeout, why);
+            }
+
+            std::thread::sleep(
+                DEVICE_POLL_INTERVAL
+                    .min(timeout.saturating_sub(start.elapsed())),
+            );
+        }
+    }
+
+    /**
+     * Find the node and minor node to which a /dev path (e.g.,
+     * "/dev/dsk/c1t0d0s0") refers, by resolving the link to its target under
+     * /devices.  Returns None if the target is not in the snapshot.
+     */
+    pub fn node_for_dev_path<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<Option<(Node<'_>, Minor<'_>)>> {
+        let path = path.as_ref();
+        let target = match std::fs::canonicalize(path) {
+            Ok(t) => t,
+            Err(e) => bail!("resolving {}: {}", path.display(), e),
+        };
+        let Some(target) = target.to_str() else {
+            bail!("{}: target is not valid UTF-8", path.display());
+        };
+
+        self.minor_by_path(target)
+    }
+
+    /**
+     * Given the public name for a disk device (e.g.,
+     * "c1t0025385C9150D623d0"), determine the driver name, instance, and
+     * minor name of the device; e.g., ("blkdev", 0, "wd").  This is the
+     * inverse of DevInstMinor::lookup_disk_name().  As with that routine,
+     * the whole disk ("wd") minor is preferred, falling back to the first
+     * slice ("a"); a name that includes a slice or partition refers only to
+     * that minor.  The name is resolved through the devlink database, so
+     * the links need not exist in the file system.  Returns None if there
+     * is no such disk.
+     */
+    pub fn parse_disk_name(
+        &self,
+        links: &DevLinks,
+        name: &str,
+    ) -> Result<Option<(String, u32, String)>> {
+        let name: DiskName = name.parse()?;
+        let candidates = if name.slice().is_some() || name.partition().is_some()
+        {
+            vec![name]
+        } else {
+            vec![name.clone(), name.with_slice(0)]
+        };
+
+        for name in candidates {
+            let link = format!("dsk/{}", name);
+            let Some((node, minor)) = links.resolve_minor(self, &link)? else {
+                continue;
+            };
+            let (Some(driver), Some(instance)) = (
+                node.driver_name(),
+                node.instance().and_then(|i| u32::try_from(i).ok()),
+            ) else {
+                continue;
+            };
+
+            return Ok(Some((driver, instance, minor.name())));
+        }
+
+        Ok(None)
+    }
+
+    /**
+     * Find the node and minor node for a devfs minor path; e.g.,
+     * "/devices/pci@0,0/pci@1,1/disk@0,0:a".  The leading "/devices" is
+     * optional.  Returns None if the minor is not in the snapshot.
+     */
+    pub fn minor_by_path(
+        &self,
+        path: &str,
+    ) -> Result<Option<(Node<'_>, Minor<'_>)>> {
+        let devfs: DevfsPath = path.parse()?;
+        let Some(minor) = devfs.minor() else {
+            bail!("{} is not the path of a minor node", path);
+        };
+
+        let Some(node) = self.node_by_path(path)? else {
+            return Ok(None);
+        };
+        for m in node.minors() {
+            let m = m?;
+            if m.name() == minor {
+                return Ok(Some((node, m)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /**
+     * Find the minor node with the given device number; e.g., the st_rdev
+     * from stat(2) of a device file.  The snapshot must include minor nodes.
+     */
+    pub fn minor_by_devt(&self, dev: dev_t) -> Result<Option<Minor<'_>>> {
+        for res in self.walk_minors(None) {
+            let (_, m) = res?;
+            if m.devt() == dev {
+                return Ok(Some(m));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /**
+     * Find the node that owns the minor node with the given device number.
+     */
+    pub fn node_by_devt(&self, dev: dev_t) -> Result<Option<Node<'_>>> {
+        Ok(self.minor_by_devt(dev)?.map(|m| m.node()))
+    }
+
+    /**
+     * Build an index of every node in the snapshot by devfs path, for
+     * consumers that look up many paths in the same snapshot.
+     */
+    pub fn path_index(&self) -> Result<HashMap<String, Node<'_>>> {
+        let mut index = HashMap::new();
+        for n in self.walk_node() {
+            let n = n?;
+            index.insert(n.devfs_path()?, n);
+        }
+        Ok(index)
     }
 
-    pub fn walk_driver(&mut self, name: &str) -> DriverWalk {
+    /**
+     * Find the node with the given encoded device ID.  An encoded devid that
+     * includes a minor name (e.g., "id1,sd@n5000c500a1b2c3d4/a", as stored by
+     * ZFS) is also accepted; the minor name is ignored.
+     */
+    pub fn node_by_devid(&self, devid: &str) -> Result<Option<Node<'_>>> {
+        let devid = devid.split_once('/').map_or(devid, |(id, _)| id);
+        for n in self.walk_node() {
+            let n = n?;
+            if n.devid().as_deref() == Some(devid) {
+                return Ok(Some(n));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /**
+     * Return the names of all of the drivers bound to nodes in the snapshot.
+     */
+    pub fn drivers(&self) -> Result<BTreeSet<String>> {
+        Ok(self.driver_counts()?.into_keys().collect())
+    }
+
+    /**
+     * Return the number of nodes bound to each driver in the snapshot.
+     */
+    pub fn driver_counts(&self) -> Result<BTreeMap<String, usize>> {
+        let mut counts = BTreeMap::new();
+        for n in self.walk_node() {
+            if let Some(driver) = n?.driver_name() {
+                *counts.entry(driver).or_insert(0) += 1;
+            }
+        }
+        Ok(counts)
+    }
+
+    pub fn walk_driver(&self, name: &str) -> DriverWalk<'_> {
         DriverWalk {
             parent: self,
             driver: name.to_string(),
@@ -112,12 +633,67 @@ impl DevInfo {
         }
     }
 
-    pub fn walk_node(&mut self) -> NodeWalk {
+    pub fn walk_node(&self) -> NodeWalk<'_> {
         NodeWalk {
             parent: self,
             node: DI_NODE_NIL,
             fin: false,
             skip_children: false,
+            skip_siblings: Vec::new(),
+        }
+    }
+
+    /**
+     * Walk every minor node in the snapshot, yielding each minor along with
+     * the node to which it belongs.  If a node type is provided (e.g.,
+     * "ddi_block"), only minors of that type, or of a more specific subtype
+     * (e.g., "ddi_block:cdrom"), are included.
+     */
+    pub fn walk_minors(&self, node_type: Option<&str>) -> AllMinorWalk<'_> {
+        AllMinorWalk {
+            nodes: self.walk_node(),
+            current: None,
+            node_type: node_type.map(str::to_string),
+        }
+    }
+}
+
+pub struct AllMinorWalk<'w> {
+    nodes: NodeWalk<'w>,
+    current: Option<(Node<'w>, MinorWalk<'w>)>,
+    node_type: Option<String>,
+}
+
+impl<'a> Iterator for AllMinorWalk<'a> {
+    type Item = Result<(Node<'a>, Minor<'a>)>;
+
+    fn next(&mut self) -> Option<Result<(Node<'a>, Minor<'a>)>> {
+        loop {
+            if let Some((node, minors)) = &mut self.current {
+                match minors.next() {
+                    Some(Ok(m)) => {
+                        if let Some(nt) = &self.node_type {
+                            if !m.node_type_parsed().is_a(nt) {
+                                continue;
+                            }
+                        }
+                        return Some(Ok((node.clone(), m)));
+                    }
+                    Some(Err(e)) => return Some(Err(e)),
+                    None => self.current = None,
+                }
+            }
+
+            /*
+             * Move on to the minors of the next node in the tree.
+             */
+            match self.nodes.next()? {
+                Ok(n) => {
+                    let minors = n.minors();
+                    self.current = Some((n, minors));
+                }
+                Err(e) => return Some(Err(e)),
+            }
         }
     }
 }
@@ -127,12 +703,42 @@ pub struct NodeWalk<'w> {
     node: *mut di_node_t,
     fin: bool,
     skip_children: bool,
+    /*
+     * The parents of nodes for which skip_siblings() was called, innermost
+     * last.  The children of these nodes that remain to be visited will be
+     * skipped.
+     */
+    skip_siblings: Vec<*mut di_node_t>,
 }
 
 impl<'a> NodeWalk<'a> {
     pub fn skip_children(&mut self) {
         self.skip_children = true;
     }
+
+    /**
+     * Skip the siblings of the most recent node that have not yet been
+     * visited.  The children of that node are still visited, unless
+     * skip_children() is also called.
+     */
+    pub fn skip_siblings(&mut self) {
+        if self.node != DI_NODE_NIL {
+            let parent = unsafe { di_parent_node(self.node) };
+            if self.skip_siblings.last() != Some(&parent) {
+                self.skip_siblings.push(parent);
+            }
+        }
+    }
+
+    fn next_sibling(&mut self, node: *mut di_node_t) -> *mut di_node_t {
+        let parent = unsafe { di_parent_node(node) };
+        if self.skip_siblings.last() == Some(&parent) {
+            self.skip_siblings.pop();
+            DI_NODE_NIL
+        } else {
+            unsafe { di_sibling_node(node) }
+        }
+    }
 }
 
 impl<'a> Iterator for NodeWalk<'a> {
@@ -174,7 +780,7 @@ impl<'a> Iterator for NodeWalk<'a> {
         /*
          * No children of this node.  Try the next sibling.
          */
-        let sib = unsafe { di_sibling_node(self.node) };
+        let sib = self.next_sibling(self.node);
         if sib != DI_NODE_NIL {
             /*
              * Visit this sibling.
@@ -194,7 +800,7 @@ impl<'a> Iterator for NodeWalk<'a> {
                 return None;
             }
 
-            let sib = unsafe { di_sibling_node(self.node) };
+            let sib = self.next_sibling(self.node);
             if sib != DI_NODE_NIL {
                 /*
                  * Visit this node.
@@ -250,6 +856,21 @@ impl<'a> Node<'a> {
             .to_string()
     }
 
+    /**
+     * Return the node name without any lossy conversion to UTF-8.
+     */
+    pub fn node_name_os(&self) -> OsString {
+        os_string(unsafe { di_node_name(self.node) })
+    }
+
+    /**
+     * Returns true if a driver is attached to this node.
+     */
+    pub fn is_attached(&self) -> bool {
+        let state = unsafe { di_state(self.node) };
+        state & DI_DRIVER_DETACHED == 0
+    }
+
     pub fn driver_name(&self) -> Option<String> {
         let v = unsafe { di_driver_name(self.node) };
         if v.is_null() {
@@ -259,6 +880,18 @@ impl<'a> Node<'a> {
         }
     }
 
+    /**
+     * Return the driver name without any lossy conversion to UTF-8.
+     */
+    pub fn driver_name_os(&self) -> Option<OsString> {
+        let v = unsafe { di_driver_name(self.node) };
+        if v.is_null() {
+            None
+        } else {
+            Some(os_string(v))
+        }
+    }
+
     pub fn instance(&self) -> Option<i32> {
         let v = unsafe { di_instance(self.node) };
         if v == -1 {
@@ -281,48 +914,217 @@ impl<'a> Node<'a> {
         Ok(s)
     }
 
-    pub fn props(&self) -> PropertyWalk {
+    /**
+     * Return the devfs path of this node without any conversion to UTF-8.
+     */
+    pub fn devfs_path_os(&self) -> Result<OsString> {
+        let p = unsafe { di_devfs_path(self.node) };
+        if p.is_null() {
+            let e = std::io::Error::last_os_error();
+            bail!("di_devfs_path failed: {}", e);
+        }
+
+        Ok(take_devfs_path(p))
+    }
+
+    /**
+     * Return the devfs path of this node, including the "/devices" prefix.
+     */
+    pub fn devfs_path_full(&self) -> Result<PathBuf> {
+        Ok(devfs_path_full(&self.devfs_path_os()?))
+    }
+
+    /**
+     * Returns true if the node currently exists in the devfs file system.
+     */
+    pub fn exists(&self) -> Result<bool> {
+        Ok(self.devfs_path_full()?.exists())
+    }
+
+    /**
+     * Return the unit address of this node; e.g., for the node
+     * "/pci@0,0/pci1022,1483@1,2", the unit address is "1,2".  Nodes that
+     * have not been assigned an address will return None.
+     */
+    pub fn unit_address(&self) -> Option<String> {
+        let v = unsafe { di_bus_addr(self.node) };
+        if !v.is_null() {
+            let a = unsafe { CStr::from_ptr(v) }.to_string_lossy();
+            if !a.is_empty() {
+                return Some(a.to_string());
+            }
+        }
+
+        /*
+         * If the bus address is not available in the snapshot, fall back to
+         * the last component of the devfs path.
+         */
+        let path = self.devfs_path().ok()?;
+        let (_, a) = path.rsplit('/').next()?.split_once('@')?;
+        if a.is_empty() {
+            None
+        } else {
+            Some(a.to_string())
+        }
+    }
+
+    fn walk_props(&self, next: PropNextFn) -> PropertyWalk<'a> {
         PropertyWalk {
             parent: self.parent,
             node: self.node,
             prop: DI_PROP_NIL,
             fin: false,
+            next,
         }
     }
 
+    /**
+     * Walk all properties of this node, regardless of their source.
+     */
+    pub fn props(&self) -> PropertyWalk<'_> {
+        self.walk_props(di_prop_next)
+    }
+
+    /**
+     * Walk only the properties created by the system for this node; e.g.,
+     * from firmware or by the bus nexus during enumeration.
+     */
+    pub fn hw_props(&self) -> PropertyWalk<'_> {
+        self.walk_props(di_prop_hw_next)
+    }
+
+    /**
+     * Walk only the properties created by the driver bound to this node.
+     */
+    pub fn drv_props(&self) -> PropertyWalk<'_> {
+        self.walk_props(di_prop_drv_next)
+    }
+
+    /**
+     * Walk only the properties created by the system framework for this
+     * node.
+     */
+    pub fn sys_props(&self) -> PropertyWalk<'_> {
+        self.walk_props(di_prop_sys_next)
+    }
+
+    /**
+     * Walk only the global properties that apply to this node, as specified
+     * in driver.conf(5) files.
+     */
+    pub fn global_props(&self) -> PropertyWalk<'_> {
+        self.walk_props(di_prop_global_next)
+    }
+
     pub fn string_props(&self) -> BTreeMap<String, String> {
         string_props(self.node)
     }
 
-    pub fn minors(&self) -> MinorWalk {
-        MinorWalk {
-            parent: self.parent,
-            node: self.node,
-            minor: DI_MINOR_NIL,
-            fin: false,
-        }
+    /**
+     * Collect all string properties of this node, including every value of
+     * multi-valued properties such as "compatible".
+     */
+    pub fn string_props_all(&self) -> BTreeMap<String, Vec<String>> {
+        string_props_all(self.node)
     }
 
-    pub fn depth(&self) -> u32 {
-        let mut d = 0;
-        let mut n = self.node;
-        loop {
-            if n == DI_NODE_NIL {
-                break;
+    /**
+     * Collect all properties of this node, with their decoded values.  If
+     * more than one property has the same name (e.g., a driver property that
+     * overrides a hardware property), the first one found is used, as it
+     * would be for a lookup by name.
+     */
+    pub fn props_map(&self) -> Result<BTreeMap<String, PropValue>> {
+        let mut out = BTreeMap::new();
+        for p in self.props() {
+            let p = p?;
+            if let btree_map::Entry::Vacant(e) = out.entry(p.name()) {
+                e.insert(p.value()?);
             }
-            n = unsafe { di_parent_node(n) };
-            d += 1;
         }
-        d
+        Ok(out)
     }
 
-    pub fn parent(&self) -> Result<Option<Node<'a>>> {
+    /**
+     * The encoded device ID of the node, if it has one; e.g.,
+     * "id1,sd@n5000c500a1b2c3d4".  This is the form recorded by ZFS in the
+     * "devid" of a vdev, without any trailing minor name.
+     */
+    pub fn devid(&self) -> Option<String> {
+        self.prop_str("devid")
+    }
+
+    /**
+     * Look up an integer property by name, returning the first value.
+     */
+    pub fn prop_i32(&self, name: &str) -> Option<i32> {
+        lookup_ints(DDI_DEV_T_ANY, self.node, name)?.first().copied()
+    }
+
+    /**
+     * Look up a 64-bit integer property by name, returning the first value.
+     */
+    pub fn prop_i64(&self, name: &str) -> Option<i64> {
+        lookup_int64s(DDI_DEV_T_ANY, self.node, name)?.first().copied()
+    }
+
+    /**
+     * Look up a string property by name, returning the first value.
+     */
+    pub fn prop_str(&self, name: &str) -> Option<String> {
+        lookup_strings(DDI_DEV_T_ANY, self.node, name)?
+            .first()?
+            .to_str()
+            .ok()
+            .map(str::to_string)
+    }
+
+    /**
+     * Look up a byte array property by name.
+     */
+    pub fn prop_bytes(&self, name: &str) -> Option<&[u8]> {
+        lookup_bytes(DDI_DEV_T_ANY, self.node, name)
+    }
+
+    /**
+     * Returns true if a boolean property of this name exists on the node.
+     * Boolean properties have no value; their presence indicates true.
+     */
+    pub fn prop_bool(&self, name: &str) -> bool {
+        lookup_ints(DDI_DEV_T_ANY, self.node, name)
+            .is_some_and(|v| v.is_empty())
+    }
+
+    pub fn minors(&self) -> MinorWalk<'a> {
+        MinorWalk {
+            parent: self.parent,
+            node: self.node,
+            minor: DI_MINOR_NIL,
+            fin: false,
+        }
+    }
+
+    pub fn depth(&self) -> u32 {
+        let mut d = 0;
+        let mut n = self.node;
+        loop {
+            if n == DI_NODE_NIL {
+                break;
+            }
+            n = unsafe { di_parent_node(n) };
+            d += 1;
+        }
+        d
+    }
+
+    pub fn parent(&self) -> Result<Option<Node<'a>>> {
         let n = unsafe { di_parent_node(self.node) };
         if n == DI_NODE_NIL {
-            if unsafe { *___errno() } == ENXIO {
+            let e = std::io::Error::last_os_error();
+            if e.raw_os_error() == Some(ENXIO) {
                 Ok(None)
             } else {
-                bail!("{}", std::io::Error::last_os_error());
+                bail!("{}", e);
             }
         } else {
             Ok(Some(Node { parent: self.parent, node: n }))
@@ -330,11 +1132,26 @@ impl<'a> Node<'a> {
     }
 }
 
+impl std::fmt::Debug for Node<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Node")
+            .field("name", &self.node_name())
+            .field("driver", &self.driver_name())
+            .field("instance", &self.instance())
+            .field("devfs_path", &self.devfs_path().ok())
+            .finish()
+    }
+}
+
+type PropNextFn =
+    unsafe extern "C" fn(*mut di_node_t, *mut di_prop_t) -> *mut di_prop_t;
+
 pub struct PropertyWalk<'p> {
     parent: &'p DevInfo,
     node: *mut di_node_t,
     prop: *mut di_prop_t,
     fin: bool,
+    next: PropNextFn,
 }
 
 impl<'a> Iterator for PropertyWalk<'a> {
@@ -345,7 +1162,7 @@ impl<'a> Iterator for PropertyWalk<'a> {
             return None;
         }
 
-        self.prop = unsafe { di_prop_next(self.node, self.prop) };
+        self.prop = unsafe { (self.next)(self.node, self.prop) };
         if self.prop == DI_PROP_NIL {
             self.fin = true;
             return None;
@@ -367,8 +1184,34 @@ impl Property<'_> {
             .to_string()
     }
 
+    /**
+     * Return the property name without allocating or converting it.
+     */
+    pub fn name_cstr(&self) -> &CStr {
+        unsafe { CStr::from_ptr(di_prop_name(self.prop)) }
+    }
+
+    /**
+     * Return the property name as bytes, without any conversion to UTF-8.
+     */
+    pub fn name_bytes(&self) -> &[u8] {
+        self.name_cstr().to_bytes()
+    }
+
+    /**
+     * Return the minor node, if any, with which this property is associated.
+     */
+    pub fn devt(&self) -> PropDevt {
+        PropDevt::from(unsafe { di_prop_devt(self.prop) })
+    }
+
     pub fn value_type(&self) -> PropType {
-        PropType::try_from(unsafe { di_prop_type(self.prop) }).unwrap()
+        /*
+         * Treat any type we do not recognise, perhaps from a newer version of
+         * the system, as unknown rather than panicking.
+         */
+        PropType::try_from(unsafe { di_prop_type(self.prop) })
+            .unwrap_or(PropType::Unknown)
     }
 
     pub fn as_i64(&self) -> Option<i64> {
@@ -402,6 +1245,60 @@ impl Property<'_> {
         }
     }
 
+    /**
+     * Return all values of a 64-bit integer property.
+     */
+    pub fn as_i64s(&self) -> Option<&[i64]> {
+        match self.value_type() {
+            PropType::Int64 => {
+                let mut data: *mut i64 = std::ptr::null_mut();
+                let n = unsafe { di_prop_int64(self.prop, &mut data) };
+                prop_slice(n, data)
+            }
+            _ => None,
+        }
+    }
+
+    /**
+     * Return all values of an integer property; e.g., "reg" or
+     * "interrupts".
+     */
+    pub fn as_i32s(&self) -> Option<&[i32]> {
+        match self.value_type() {
+            PropType::Int32 => {
+                let mut data: *mut c_int = std::ptr::null_mut();
+                let n = unsafe { di_prop_ints(self.prop, &mut data) };
+                prop_slice(n, data)
+            }
+            _ => None,
+        }
+    }
+
+    /**
+     * Return all values of a string property; e.g., "compatible".
+     */
+    pub fn as_cstrs(&self) -> Option<Vec<&CStr>> {
+        match self.value_type() {
+            PropType::String => {
+                let mut data: *mut c_char = std::ptr::null_mut();
+                let n = unsafe { di_prop_strings(self.prop, &mut data) };
+                prop_strings(n, data)
+            }
+            _ => None,
+        }
+    }
+
+    /**
+     * Return all values of a string property, or None if any value is not
+     * valid UTF-8.
+     */
+    pub fn strings(&self) -> Option<Vec<String>> {
+        self.as_cstrs()?
+            .into_iter()
+            .map(|s| s.to_str().ok().map(str::to_string))
+            .collect()
+    }
+
     pub fn to_str(&self) -> Option<String> {
         self.as_cstr()?.to_str().ok().map(|s| s.to_string())
     }
@@ -437,17 +1334,251 @@ impl Property<'_> {
             _ => None,
         }
     }
+
+    /**
+     * Decode the value of the property according to its type.
+     */
+    pub fn value(&self) -> Result<PropValue> {
+        let v = decode_prop_value(
+            self.value_type(),
+            || self.as_i32s(),
+            || self.as_i64s(),
+            || self.as_cstrs(),
+            || self.bytes(),
+        );
+
+        match v {
+            Some(v) => Ok(v),
+            None => {
+                let e = std::io::Error::last_os_error();
+                bail!("could not decode property {:?}: {}", self.name(), e);
+            }
+        }
+    }
+
+    /**
+     * Return the raw property payload as bytes, regardless of the declared
+     * type of the property.  This is useful for properties published with
+     * the wrong type by a driver.  Integer values are in native byte order,
+     * and string values each include their NUL terminator.
+     */
+    pub fn raw_bytes(&self) -> Option<&[u8]> {
+        #[cfg(feature = "private")]
+        {
+            let mut data: *mut c_uchar = std::ptr::null_mut();
+            let n = unsafe { di_prop_rawdata(self.prop, &mut data) };
+            prop_slice(n, data)
+        }
+        #[cfg(not(feature = "private"))]
+        {
+            self.raw_bytes_typed()
+        }
+    }
+
+    /*
+     * Without access to di_prop_rawdata(), reconstruct a view of the payload
+     * from the typed accessors.
+     */
+    #[cfg(not(feature = "private"))]
+    fn raw_bytes_typed(&self) -> Option<&[u8]> {
+        fn as_bytes<T>(v: &[T]) -> &[u8] {
+            unsafe {
+                std::slice::from_raw_parts(
+                    v.as_ptr() as *const u8,
+                    std::mem::size_of_val(v),
+                )
+            }
+        }
+
+        match self.value_type() {
+            PropType::Boolean | PropType::Undefined => Some(&[]),
+            PropType::Int32 => self.as_i32s().map(as_bytes),
+            PropType::Int64 => self.as_i64s().map(as_bytes),
+            PropType::String => {
+                /*
+                 * String values are packed contiguously, so the payload
+                 * extends from the start of the first value to the end of
+                 * the last.
+                 */
+                let v = self.as_cstrs()?;
+                let Some(first) = v.first() else {
+                    return Some(&[]);
+                };
+                let len = v.iter().map(|s| s.to_bytes_with_nul().len()).sum();
+                Some(unsafe {
+                    std::slice::from_raw_parts(first.as_ptr() as *const u8, len)
+                })
+            }
+            PropType::Byte | PropType::Unknown => self.bytes(),
+        }
+    }
+
+    /*
+     * The di_prop_bytes(3DEVINFO) interface will return the data for either
+     * byte or unknown properties.
+     */
+    fn bytes(&self) -> Option<&[u8]> {
+        let mut data: *mut c_uchar = std::ptr::null_mut();
+        let n = unsafe { di_prop_bytes(self.prop, &mut data) };
+        prop_slice(n, data)
+    }
+}
+
+/*
+ * Decode the value of a property of the given type, using the provided
+ * accessors for the data.  This is shared by the different kinds of property
+ * (e.g., those of nodes and those of paths), which have their own accessor
+ * functions in libdevinfo.  The byte accessor is used for properties of
+ * unknown type as well as for byte arrays.  Returns None if the accessor for
+ * the type fails.
+ */
+pub(crate) fn decode_prop_value<'a>(
+    ptype: PropType,
+    ints: impl FnOnce() -> Option<&'a [i32]>,
+    int64s: impl FnOnce() -> Option<&'a [i64]>,
+    strings: impl FnOnce() -> Option<Vec<&'a CStr>>,
+    bytes: impl FnOnce() -> Option<&'a [u8]>,
+) -> Option<PropValue> {
+    match ptype {
+        PropType::Boolean => Some(PropValue::Bool),
+        PropType::Undefined => Some(PropValue::Undefined),
+        PropType::Int32 => ints().map(|v| PropValue::Ints(v.to_vec())),
+        PropType::Int64 => int64s().map(|v| PropValue::Int64s(v.to_vec())),
+        PropType::String => strings().map(|v| {
+            PropValue::Strings(
+                v.iter().map(|s| s.to_string_lossy().to_string()).collect(),
+            )
+        }),
+        PropType::Byte => bytes().map(|v| PropValue::Bytes(v.to_vec())),
+        PropType::Unknown => bytes().map(|v| PropValue::Unknown(v.to_vec())),
+    }
+}
+
+/**
+ * The decoded value of a property, as returned by Property::value().
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PropValue {
+    /**
+     * A boolean property has no value; its presence indicates true.
+     */
+    Bool,
+    Ints(Vec<i32>),
+    Int64s(Vec<i64>),
+    Strings(Vec<String>),
+    Bytes(Vec<u8>),
+    /**
+     * The raw data of a property whose type is not known to the system.
+     */
+    Unknown(Vec<u8>),
+    /**
+     * The property has been explicitly undefined by the driver.
+     */
+    Undefined,
 }
 
 impl std::fmt::Display for Property<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self.value_type() {
-            PropType::Int32 => write!(f, "{}", self.as_i32().unwrap()),
-            PropType::Int64 => write!(f, "{}", self.as_i64().unwrap()),
+            PropType::Int32 => {
+                if let Some(v) = self.as_i32() {
+                    return write!(f, "{}", v);
+                }
+            }
+            PropType::Int64 => {
+                if let Some(v) = self.as_i64() {
+                    return write!(f, "{}", v);
+                }
+            }
             PropType::String => {
-                write!(f, "{}", self.as_cstr().unwrap().to_str().unwrap())
+                if let Some(v) = self.as_cstr() {
+                    return write!(f, "{}", v.to_string_lossy());
+                }
+            }
+            PropType::Byte => {
+                /*
+                 * Render byte arrays (e.g., "wwn" or "devid") as a string of
+                 * hexadecimal digits.
+                 */
+                if let Some(v) = self.as_bytes() {
+                    for b in v {
+                        write!(f, "{:02x}", b)?;
+                    }
+                    return Ok(());
+                }
             }
-            _ => write!(f, "<?Property>"),
+            PropType::Boolean => return write!(f, "{}", self.name()),
+            _ => (),
+        }
+
+        /*
+         * Either the type is not one we can render, or the value could not
+         * be decoded.
+         */
+        write!(f, "<?Property>")
+    }
+}
+
+impl std::fmt::Debug for Property<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Property")
+            .field("name", &self.name())
+            .field("devt", &self.devt())
+            .field("type", &self.value_type())
+            .field("value", &format_args!("{}", self))
+            .finish()
+    }
+}
+
+/**
+ * The association between a property and the minor nodes of its device, as
+ * returned by di_prop_devt(3DEVINFO).
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PropDevt {
+    /**
+     * The property applies to the node as a whole (DDI_DEV_T_NONE).
+     */
+    None,
+    /**
+     * The property applies to any minor node of the device (DDI_DEV_T_ANY).
+     */
+    Any,
+    /**
+     * The property applies to the minor node with this device number.
+     */
+    Dev(dev_t),
+}
+
+impl PropDevt {
+    pub fn is_none(&self) -> bool {
+        matches!(self, PropDevt::None)
+    }
+
+    pub fn is_any(&self) -> bool {
+        matches!(self, PropDevt::Any)
+    }
+
+    /**
+     * The device number of the minor node with which the property is
+     * associated, if it is associated with a particular one.
+     */
+    pub fn dev(&self) -> Option<dev_t> {
+        match self {
+            PropDevt::Dev(dev) => Some(*dev),
+            PropDevt::None | PropDevt::Any => None,
+        }
+    }
+}
+
+impl From<dev_t> for PropDevt {
+    fn from(dev: dev_t) -> Self {
+        match dev {
+            DDI_DEV_T_NONE => PropDevt::None,
+            DDI_DEV_T_ANY => PropDevt::Any,
+            dev => PropDevt::Dev(dev),
         }
     }
 }
@@ -473,16 +1604,17 @@ impl<'a> Iterator for MinorWalk<'a> {
             return None;
         }
 
-        Some(Ok(Minor { _parent: self.parent, minor: self.minor }))
+        Some(Ok(Minor { parent: self.parent, minor: self.minor }))
     }
 }
 
 pub struct Minor<'p> {
-    _parent: &'p DevInfo,
+    parent: &'p DevInfo,
     minor: *mut di_minor_t,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SpecType {
     Char,
     Block,
@@ -495,20 +1627,134 @@ impl<'a> Minor<'a> {
             .to_string()
     }
 
+    /**
+     * Return the minor name without any lossy conversion to UTF-8.
+     */
+    pub fn name_os(&self) -> OsString {
+        os_string(unsafe { di_minor_name(self.minor) })
+    }
+
     pub fn node_type(&self) -> String {
         unsafe { CStr::from_ptr(di_minor_nodetype(self.minor)) }
             .to_string_lossy()
             .to_string()
     }
 
+    pub fn node_type_parsed(&self) -> MinorNodeType {
+        MinorNodeType::from(self.node_type().as_str())
+    }
+
     pub fn spec_type(&self) -> SpecType {
-        match unsafe { di_minor_spectype(self.minor) as libc::mode_t } {
-            libc::S_IFCHR => SpecType::Char,
-            libc::S_IFBLK => SpecType::Block,
-            other => panic!("unknown spectype 0x{:x}", other),
+        match self.try_spec_type() {
+            Some(t) => t,
+            None => panic!("unknown spectype 0x{:x}", self.raw_spec_type()),
         }
     }
 
+    /**
+     * Like spec_type(), but returns None rather than panicking if the type
+     * is not one known to this crate.
+     */
+    pub fn try_spec_type(&self) -> Option<SpecType> {
+        match self.raw_spec_type() {
+            libc::S_IFCHR => Some(SpecType::Char),
+            libc::S_IFBLK => Some(SpecType::Block),
+            _ => None,
+        }
+    }
+
+    fn raw_spec_type(&self) -> libc::mode_t {
+        unsafe { di_minor_spectype(self.minor) as libc::mode_t }
+    }
+
+    /*
+     * Properties published for a specific minor node are associated with
+     * its dev_t, and are looked up through the owning node.
+     */
+    fn lookup_scope(&self) -> (dev_t, *mut di_node_t) {
+        (self.devt(), self.node().node)
+    }
+
+    /**
+     * Return the node to which this minor node belongs.
+     */
+    pub fn node(&self) -> Node<'a> {
+        Node {
+            parent: self.parent,
+            node: unsafe { di_minor_devinfo(self.minor) },
+        }
+    }
+
+    /**
+     * Return the device number (dev_t) of this minor node, as would be
+     * reported in the st_rdev member by stat(2) for the device special file.
+     */
+    pub fn devt(&self) -> dev_t {
+        unsafe { di_minor_devt(self.minor) }
+    }
+
+    /**
+     * Return the major number of this minor node.
+     */
+    pub fn major(&self) -> u32 {
+        devt_major(self.devt())
+    }
+
+    /**
+     * Return the minor number of this minor node.
+     */
+    pub fn minor(&self) -> u32 {
+        devt_minor(self.devt())
+    }
+
+    /**
+     * Look up an integer property associated with this minor node,
+     * returning the first value.
+     */
+    pub fn prop_i32(&self, name: &str) -> Option<i32> {
+        let (dev, node) = self.lookup_scope();
+        lookup_ints(dev, node, name)?.first().copied()
+    }
+
+    /**
+     * Look up a 64-bit integer property associated with this minor node,
+     * returning the first value.
+     */
+    pub fn prop_i64(&self, name: &str) -> Option<i64> {
+        let (dev, node) = self.lookup_scope();
+        lookup_int64s(dev, node, name)?.first().copied()
+    }
+
+    /**
+     * Look up a string property associated with this minor node, returning
+     * the first value.
+     */
+    pub fn prop_str(&self, name: &str) -> Option<String> {
+        let (dev, node) = self.lookup_scope();
+        lookup_strings(dev, node, name)?
+            .first()?
+            .to_str()
+            .ok()
+            .map(str::to_string)
+    }
+
+    /**
+     * Look up a byte array property associated with this minor node.
+     */
+    pub fn prop_bytes(&self, name: &str) -> Option<&[u8]> {
+        let (dev, node) = self.lookup_scope();
+        lookup_bytes(dev, node, name)
+    }
+
+    /**
+     * Returns true if a boolean property of this name is associated with
+     * this minor node.
+     */
+    pub fn prop_bool(&self, name: &str) -> bool {
+        let (dev, node) = self.lookup_scope();
+        lookup_ints(dev, node, name).is_some_and(|v| v.is_empty())
+    }
+
     pub fn devfs_path(&self) -> Result<String> {
         let p = unsafe { di_devfs_minor_path(self.minor) };
         if p.is_null() {
@@ -521,23 +1767,77 @@ impl<'a> Minor<'a> {
         unsafe { di_devfs_path_free(p) };
         Ok(s)
     }
+
+    /**
+     * Return the devfs path of this minor node without any conversion to
+     * UTF-8.
+     */
+    pub fn devfs_path_os(&self) -> Result<OsString> {
+        let p = unsafe { di_devfs_minor_path(self.minor) };
+        if p.is_null() {
+            let e = std::io::Error::last_os_error();
+            bail!("di_devfs_minor_path failed: {}", e);
+        }
+
+        Ok(take_devfs_path(p))
+    }
+
+    /**
+     * Return the devfs path of this minor node, including the "/devices"
+     * prefix; e.g., "/devices/pseudo/clone@0:ipnet".
+     */
+    pub fn devfs_path_full(&self) -> Result<PathBuf> {
+        Ok(devfs_path_full(&self.devfs_path_os()?))
+    }
+
+    /**
+     * Returns true if the device special file for this minor node currently
+     * exists in the devfs file system.
+     */
+    pub fn exists(&self) -> Result<bool> {
+        Ok(self.devfs_path_full()?.exists())
+    }
+}
+
+impl std::fmt::Debug for Minor<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Minor")
+            .field("name", &self.name())
+            .field("node_type", &self.node_type())
+            .field("spec_type", &self.try_spec_type())
+            .field("devfs_path", &self.devfs_path().ok())
+            .finish()
+    }
 }
 
 pub struct DevLinks {
     handle: *mut di_devlink_handle_t,
+    /*
+     * The alternate root directory, if the handle was opened with
+     * di_devlink_open() rather than di_devlink_init().
+     */
+    root: Option<PathBuf>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DevLinkType {
     Primary,
     Secondary,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DevLink {
     path: PathBuf,
     content: PathBuf,
     linktype: DevLinkType,
+    /*
+     * The alternate root directory of the handle through which the link was
+     * found, if any.  The path of the link is within this root.
+     */
+    #[cfg_attr(feature = "serde", serde(default))]
+    root: Option<PathBuf>,
 }
 
 impl DevLink {
@@ -552,97 +1852,1044 @@ impl DevLink {
     pub fn linktype(&self) -> DevLinkType {
         self.linktype
     }
+
+    /**
+     * Check whether the link currently exists in the file system.  The link
+     * itself is examined, not its target.
+     */
+    pub fn exists(&self) -> bool {
+        self.path.symlink_metadata().is_ok()
+    }
+
+    /**
+     * Resolve the link in the file system, following any further links, to
+     * produce the absolute path of the device file to which it refers.
+     */
+    pub fn resolved_target(&self) -> Result<PathBuf> {
+        match std::fs::canonicalize(&self.path) {
+            Ok(p) => Ok(p),
+            Err(e) => bail!("resolving {}: {}", self.path.display(), e),
+        }
+    }
+
+    /**
+     * Determine the absolute path to which the link refers from its content
+     * (e.g., "../../devices/pci@0,0/pci@1,1/disk@0,0:a"), without reference
+     * to the file system, so the link need not exist.  For a primary link,
+     * this is the path of a minor node under /devices.  A secondary link
+     * usually refers to another link in /dev, the path of which is returned
+     * instead; see DevLinks::resolve() to follow it.  Absolute content is
+     * returned as it is.  For a link found through a handle with an
+     * alternate root, the path is within that root, as is the path of the
+     * link itself.
+     */
+    pub fn canonical_target(&self) -> PathBuf {
+        let root = self.root.as_deref();
+        if self.content.is_absolute() {
+            /*
+             * Absolute content refers to a path within the root.
+             */
+            return rooted(root, &self.content);
+        }
+
+        let target = link_target_path(&self.path, &self.content);
+        let devfs = rooted(root, Path::new(DEVFS_ROOT));
+        if target.starts_with(&devfs)
+            || target.starts_with(rooted(root, Path::new(DEV_ROOT)))
+        {
+            return target;
+        }
+
+        /*
+         * Relative content that leads out of both /dev and /devices names a
+         * devfs path without the leading "/devices".
+         */
+        match root.and_then(|r| target.strip_prefix(r).ok()) {
+            Some(rel) => devfs.join(rel),
+            None => rooted(root, &devfs_path_full(target.as_os_str())),
+        }
+    }
+
+    /**
+     * Check whether the target of the link is missing from the file system;
+     * e.g., because the device has been removed.  The target is determined
+     * from the content of the link in the database, so this does not depend
+     * on the link itself existing.
+     */
+    pub fn is_dangling(&self) -> Result<bool> {
+        let target = self.canonical_target();
+        match std::fs::metadata(&target) {
+            Ok(_) => Ok(false),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(true),
+            Err(e) => bail!("stat {}: {}", target.display(), e),
+        }
+    }
+
+    /**
+     * Return the device number of the device file to which the link refers,
+     * as found in the file system.
+     */
+    pub fn devt(&self) -> Result<dev_t> {
+        match std::fs::metadata(&self.path) {
+            Ok(md) => Ok(md.rdev() as dev_t),
+            Err(e) => bail!("stat {}: {}", self.path.display(), e),
+        }
+    }
 }
 
-extern "C" fn devlink_accumulate(
+/*
+ * The type of the function through which each link found by a devlink walk
+ * is passed back to Rust code.
+ */
+type DevLinkFn<'f> = dyn FnMut(DevLink) -> ControlFlow<()> + 'f;
+
+/*
+ * The state of a devlink walk, a pointer to which is passed through
+ * di_devlink_walk() to devlink_callback().
+ */
+struct DevLinkWalkState<'a, 'f> {
+    func: &'a mut DevLinkFn<'f>,
+    /*
+     * If the callback panics, the walk is terminated and the panic is stored
+     * here to be resumed once we are no longer within libdevinfo.  Unwinding
+     * through the C frames of the walk would be undefined behaviour.
+     */
+    panic: Option<Box<dyn std::any::Any + Send>>,
+    /*
+     * Entries in the database that could not be decoded as links.
+     */
+    invalid: Vec<InvalidDevLink>,
+    root: Option<PathBuf>,
+}
+
+/**
+ * An entry in the devlink database that could not be decoded, and was thus
+ * skipped by a walk; e.g., one with a missing path or content, or a link type
+ * we do not recognise.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidDevLink {
+    pub path: Option<PathBuf>,
+    pub content: Option<PathBuf>,
+    pub linktype: c_int,
+}
+
+impl std::fmt::Display for InvalidDevLink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.path {
+            Some(p) => write!(f, "invalid devlink {}", p.display())?,
+            None => write!(f, "invalid devlink with no path")?,
+        }
+        if self.content.is_none() {
+            write!(f, ": no content")?;
+        }
+        let ltype = self.linktype as c_uint;
+        if ltype != DI_PRIMARY_LINK && ltype != DI_SECONDARY_LINK {
+            write!(f, ": unknown link type 0x{:x}", ltype)?;
+        }
+        Ok(())
+    }
+}
+
+/**
+ * The links found by a walk, along with any entries that were skipped because
+ * they could not be decoded.
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DevLinkReport {
+    pub links: Vec<DevLink>,
+    pub invalid: Vec<InvalidDevLink>,
+}
+
+fn cstr_path(p: *const c_char) -> Option<PathBuf> {
+    if p.is_null() {
+        None
+    } else {
+        Some(PathBuf::from(OsStr::from_bytes(
+            unsafe { CStr::from_ptr(p) }.to_bytes(),
+        )))
+    }
+}
+
+extern "C" fn devlink_callback(
     link: *const di_devlink_t,
     arg: *mut c_void,
 ) -> c_int {
-    let path = unsafe { di_devlink_path(link) };
-    let content = unsafe { di_devlink_content(link) };
-    let ltype = unsafe { di_devlink_type(link) } as u32;
-    if path.is_null()
-        || content.is_null()
-        || (ltype != DI_PRIMARY_LINK && ltype != DI_SECONDARY_LINK)
-    {
-        /*
-         * XXX Report an error, probably?
-         */
-        return DI_WALK_CONTINUE;
-    }
+    let state = unsafe { &mut *(arg as *mut DevLinkWalkState) };
 
-    let mut out = unsafe { Box::from_raw(arg as *mut Vec<DevLink>) };
+    let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let path = cstr_path(unsafe { di_devlink_path(link) });
+        let content = cstr_path(unsafe { di_devlink_content(link) });
+        let ltype = unsafe { di_devlink_type(link) };
 
-    out.push(DevLink {
-        path: PathBuf::from(OsStr::from_bytes(
-            unsafe { CStr::from_ptr(path) }.to_bytes(),
-        )),
-        content: PathBuf::from(OsStr::from_bytes(
-            unsafe { CStr::from_ptr(content) }.to_bytes(),
-        )),
-        linktype: match ltype {
-            DI_PRIMARY_LINK => DevLinkType::Primary,
-            DI_SECONDARY_LINK => DevLinkType::Secondary,
-            other => panic!("what is link type 0x{:x}?", other),
-        },
-    });
-    assert_eq!(Box::into_raw(out) as *mut c_void, arg);
+        let linktype = match ltype as c_uint {
+            DI_PRIMARY_LINK => Some(DevLinkType::Primary),
+            DI_SECONDARY_LINK => Some(DevLinkType::Secondary),
+            _ => None,
+        };
+
+        let (Some(path), Some(content), Some(linktype)) =
+            (path.clone(), content.clone(), linktype)
+        else {
+            state.invalid.push(InvalidDevLink {
+                path,
+                content,
+                linktype: ltype,
+            });
+            return ControlFlow::Continue(());
+        };
+
+        let link =
+            DevLink { path, content, linktype, root: state.root.clone() };
 
-    DI_WALK_CONTINUE
+        (state.func)(link)
+    }));
+
+    match res {
+        Ok(ControlFlow::Continue(())) => DI_WALK_CONTINUE,
+        Ok(ControlFlow::Break(())) => DI_WALK_TERMINATE,
+        Err(panic) => {
+            state.panic = Some(panic);
+            DI_WALK_TERMINATE
+        }
+    }
 }
 
 impl DevLinks {
-    fn new_common(make_link: bool) -> Result<DevLinks> {
+    fn new_common(name: Option<&str>, make_link: bool) -> Result<DevLinks> {
+        check_platform()?;
+        let name = name.map(CString::new).transpose()?;
         let mut flags = 0;
         if make_link {
             flags |= DI_MAKE_LINK;
         }
 
-        let handle = unsafe { di_devlink_init(std::ptr::null(), flags) };
+        let handle = unsafe {
+            di_devlink_init(
+                name.as_ref().map_or(std::ptr::null(), |n| n.as_ptr()),
+                flags,
+            )
+        };
         if handle == DI_LINK_NIL {
             let e = std::io::Error::last_os_error();
+            if matches!(e.raw_os_error(), Some(EBUSY | EAGAIN)) {
+                return Err(DevLinksBusy { waited: Duration::ZERO }.into());
+            }
             bail!("di_devlink_init: {}", e);
         }
 
-        Ok(DevLinks { handle })
+        Ok(DevLinks { handle, root: None })
+    }
+
+    #[cfg(feature = "private")]
+    fn open_common(root: &Path) -> Result<DevLinks> {
+        check_platform()?;
+        let croot = CString::new(root.as_os_str().as_bytes())?;
+
+        let handle = unsafe { di_devlink_open(croot.as_ptr(), 0) };
+        if handle == DI_LINK_NIL {
+            let e = std::io::Error::last_os_error();
+            bail!("di_devlink_open({}): {}", root.display(), e);
+        }
+
+        Ok(DevLinks { handle, root: Some(root.to_path_buf()) })
     }
 
+    /**
+     * Open the devlink database of an alternate root directory; e.g., an
+     * image or install target mounted at "/a".  Links cannot be created in
+     * an alternate root.
+     */
+    #[cfg(feature = "private")]
+    pub fn new_with_root<P: AsRef<Path>>(root: P) -> Result<Self> {
+        Self::open_common(root.as_ref())
+    }
+
+    fn new_retry(
+        name: Option<&str>,
+        make_link: bool,
+        timeout: Duration,
+    ) -> Result<DevLinks> {
+        let start = Instant::now();
+        let mut delay = DEVLINK_RETRY_MIN;
+
+        loop {
+            match Self::new_common(name, make_link) {
+                Ok(dl) => return Ok(dl),
+                Err(e) if e.is::<DevLinksBusy>() => (),
+                Err(e) => return Err(e),
+            }
+
+            let waited = start.elapsed();
+            if waited >= timeout {
+                return Err(DevLinksBusy { waited }.into());
+            }
+
+            std::thread::sleep(delay.min(timeout - waited));
+            delay = (delay * 2).min(DEVLINK_RETRY_MAX);
+        }
+    }
+
+    pub fn builder() -> DevLinksBuilder {
+        DevLinksBuilder::default()
+    }
+
+    /**
+     * Create a handle as with new(), but if the devlink database is busy
+     * (e.g., because devfsadm holds the lock) try again, backing off
+     * between attempts, until the timeout expires.  If the database is still
+     * busy, the error will be a DevLinksBusy, which may be distinguished from
+     * other failures with anyhow::Error::downcast_ref().
+     */
+    pub fn new_with_timeout(
+        make_link: bool,
+        timeout: Duration,
+    ) -> Result<Self> {
+        Self::new_retry(None, make_link, timeout)
+    }
+
+    /**
+     * Create a handle for looking up links.  Creating a handle requires
+     * loading the devlink database, and may require waiting for devfsadm, so
+     * it is relatively expensive: a handle should be created once and used
+     * for many lookups, rather than created for each lookup.  See also
+     * DevLinks::shared().
+     */
     pub fn new(make_link: bool) -> Result<Self> {
-        Self::new_common(make_link)
+        Self::new_common(None, make_link)
     }
 
-    pub fn links_for_path<P: AsRef<Path>>(&self, p: P) -> Result<Vec<DevLink>> {
-        let out: Box<Vec<DevLink>> = Default::default();
-        let arg = Box::into_raw(out);
-        let mpath = CString::new(p.as_ref().as_os_str().as_bytes()).unwrap();
+    /**
+     * Return a handle shared by the whole process, creating it on first use.
+     * The handle reflects the devlink database as it was when the handle was
+     * created, so consumers that need to see links created later should use
+     * their own handle.
+     */
+    pub fn shared() -> Result<&'static Mutex<DevLinks>> {
+        static SHARED: OnceLock<Mutex<DevLinks>> = OnceLock::new();
+
+        if let Some(dl) = SHARED.get() {
+            return Ok(dl);
+        }
+
+        /*
+         * If another thread gets here first, the handle we create will be
+         * discarded in favour of the one it created.
+         */
+        let dl = Self::new_common(None, false)?;
+        SHARED.set(Mutex::new(dl)).ok();
+        Ok(SHARED.get().unwrap())
+    }
+
+    fn walk_common(
+        &self,
+        re: Option<&str>,
+        mpath: Option<&Path>,
+        linktype: Option<DevLinkType>,
+        func: &mut DevLinkFn,
+    ) -> Result<Vec<InvalidDevLink>> {
+        /*
+         * Passing neither flag includes links of both types.
+         */
+        let flags = match linktype {
+            None => 0,
+            Some(DevLinkType::Primary) => DI_PRIMARY_LINK,
+            Some(DevLinkType::Secondary) => DI_SECONDARY_LINK,
+        };
+
+        let re = re.map(CString::new).transpose()?;
+        let mpath = mpath
+            .map(|p| CString::new(p.as_os_str().as_bytes()))
+            .transpose()?;
+
+        let mut state = DevLinkWalkState {
+            func,
+            panic: None,
+            invalid: Vec::new(),
+            root: self.root.clone(),
+        };
+        let arg = &mut state as *mut DevLinkWalkState;
 
         let r = unsafe {
             di_devlink_walk(
                 self.handle,
-                std::ptr::null(),
-                mpath.as_ptr(),
-                0,
+                re.as_ref().map_or(std::ptr::null(), |re| re.as_ptr()),
+                mpath.as_ref().map_or(std::ptr::null(), |p| p.as_ptr()),
+                flags,
                 arg as *mut c_void,
-                devlink_accumulate,
+                devlink_callback,
             )
         };
 
-        /*
-         * Make sure we get our boxed argument back so that it will be freed:
-         */
-        let out = unsafe { Box::from_raw(arg) };
+        if let Some(panic) = state.panic {
+            std::panic::resume_unwind(panic);
+        }
 
         if r != 0 {
             let e = std::io::Error::last_os_error();
             bail!("di_devlink_walk: {}", e);
         }
 
-        Ok(out.to_vec())
+        Ok(state.invalid)
+    }
+
+    fn collect_common(
+        &self,
+        re: Option<&str>,
+        mpath: Option<&Path>,
+        linktype: Option<DevLinkType>,
+    ) -> Result<DevLinkReport> {
+        let mut links = Vec::new();
+        let invalid = self.walk_common(re, mpath, linktype, &mut |l| {
+            links.push(l);
+            ControlFlow::Continue(())
+        })?;
+        Ok(DevLinkReport { links, invalid })
+    }
+
+    /**
+     * Pass each link for a minor path, or every link if no path is provided,
+     * to the provided function.  The walk ends early if the function returns
+     * ControlFlow::Break.  Any entries that cannot be decoded are skipped, and
+     * are returned once the walk is complete.
+     */
+    pub fn walk<F>(
+        &self,
+        mpath: Option<&Path>,
+        mut func: F,
+    ) -> Result<Vec<InvalidDevLink>>
+    where
+        F: FnMut(DevLink) -> ControlFlow<()>,
+    {
+        self.walk_common(None, mpath, None, &mut func)
+    }
+
+    pub fn links_for_path<P: AsRef<Path>>(&self, p: P) -> Result<Vec<DevLink>> {
+        Ok(self.collect_common(None, Some(p.as_ref()), None)?.links)
+    }
+
+    /**
+     * Return the links for each of a set of minor paths, in the same order as
+     * the paths.  This uses only the one handle, which is much cheaper than
+     * creating a handle for each path.
+     */
+    pub fn links_for_paths<P: AsRef<Path>>(
+        &self,
+        paths: &[P],
+    ) -> Result<Vec<Vec<DevLink>>> {
+        paths.iter().map(|p| self.links_for_path(p)).collect()
+    }
+
+    /**
+     * Return the links for every minor node of a node, keyed by minor name.
+     * Minor nodes without any links are included, with no links.
+     */
+    pub fn links_for_node(
+        &self,
+        node: &Node,
+    ) -> Result<BTreeMap<String, Vec<DevLink>>> {
+        let mut out = BTreeMap::new();
+        for m in node.minors() {
+            let m = m?;
+            out.insert(m.name(), self.links_for_path(m.devfs_path()?)?);
+        }
+        Ok(out)
+    }
+
+    /**
+     * Return the links for a minor path, as with links_for_path(), along with
+     * any entries for that path in the database that could not be decoded.
+     */
+    pub fn links_for_path_report<P: AsRef<Path>>(
+        &self,
+        p: P,
+    ) -> Result<DevLinkReport> {
+        self.collect_common(None, Some(p.as_ref()), None)
+    }
+
+    /**
+     * Return only the links of the given type for a minor path; e.g., just
+     * the primary links, which are the canonical names for the minor.
+     */
+    pub fn links_of_type<P: AsRef<Path>>(
+        &self,
+        p: P,
+        linktype: DevLinkType,
+    ) -> Result<Vec<DevLink>> {
+        Ok(self.collect_common(None, Some(p.as_ref()), Some(linktype))?.links)
+    }
+
+    /**
+     * Return the links for a minor path (as with links_for_path()) whose
+     * path, relative to /dev, matches the provided regular expression; e.g.,
+     * "^dsk/" or "^term/".  The expression is an extended regular expression
+     * as described in regex(7), and is evaluated by libdevinfo.
+     */
+    pub fn links_matching<P: AsRef<Path>>(
+        &self,
+        pattern: &str,
+        p: P,
+    ) -> Result<Vec<DevLink>> {
+        Ok(self.collect_common(Some(pattern), Some(p.as_ref()), None)?.links)
+    }
+
+    /**
+     * Return an iterator over the links for a minor path, as with
+     * links_for_path().  libdevinfo only provides a callback-based walk, so
+     * the links are collected by a single walk of this handle when the
+     * iterator is first advanced, and then returned one at a time; this
+     * defers the walk, but holds every link at once.  To process a large
+     * number of links without holding them all, use walk() instead.
+     * Entries that cannot be decoded are available from
+     * DevLinkIter::invalid() once the walk has been made.
+     */
+    pub fn iter_links_for_path<P: AsRef<Path>>(&self, p: P) -> DevLinkIter<'_> {
+        DevLinkIter {
+            links: self,
+            mpath: p.as_ref().to_path_buf(),
+            buf: VecDeque::new(),
+            done: false,
+            invalid: Vec::new(),
+        }
+    }
+
+    /**
+     * Find the /devices path of the minor node to which a /dev link (e.g.,
+     * "/dev/dsk/c1t0d0s0", or just "dsk/c1t0d0s0") refers, using the devlink
+     * database rather than the file system.  A secondary link is followed to
+     * the primary link it names.  Returns None if there is no such link.
+     */
+    pub fn resolve<P: AsRef<Path>>(&self, p: P) -> Result<Option<PathBuf>> {
+        let dev = self.rooted(DEV_ROOT);
+        let (mut link, _) = self.dev_path(p.as_ref())?;
+
+        /*
+         * Secondary links may refer to other links, but only ever a few deep.
+         * Guard against a loop in a damaged database.
+         */
+        for _ in 0..DEVLINK_MAX_DEPTH {
+            let (_, rel) = self.dev_path(&link)?;
+            let re = format!("^{}$", regex_escape(&rel));
+
+            let mut found = None;
+            self.walk_common(Some(&re), None, None, &mut |l| {
+                found = Some(l);
+                ControlFlow::Break(())
+            })?;
+            let Some(found) = found else {
+                return Ok(None);
+            };
+
+            let target = found.canonical_target();
+            if target.starts_with(&dev) {
+                link = target;
+            } else {
+                return Ok(Some(target));
+            }
+        }
+
+        bail!("too many levels of links resolving {}", p.as_ref().display());
+    }
+
+    /**
+     * Find the node and minor node in a snapshot to which a /dev link refers,
+     * as with resolve().  For a handle with an alternate root, the snapshot
+     * should be of the system installed in that root.
+     */
+    pub fn resolve_minor<'a, P: AsRef<Path>>(
+        &self,
+        di: &'a DevInfo,
+        p: P,
+    ) -> Result<Option<(Node<'a>, Minor<'a>)>> {
+        let Some(target) = self.resolve(p)? else {
+            return Ok(None);
+        };
+        let target = match &self.root {
+            Some(root) => match target.strip_prefix(root) {
+                Ok(rel) => Path::new("/").join(rel),
+                Err(_) => target,
+            },
+            None => target,
+        };
+        let Some(target) = target.to_str() else {
+            bail!("{} is not valid UTF-8", target.display());
+        };
+
+        di.minor_by_path(target)
+    }
+
+    /**
+     * Return every link within a subdirectory of /dev; e.g., "dsk" (or
+     * "/dev/dsk") for the block disk links.  Only that part of the database
+     * is walked.
+     */
+    pub fn links_in_dir<D: AsRef<Path>>(&self, dir: D) -> Result<Vec<DevLink>> {
+        let (dir, rel) = self.dev_path(dir.as_ref())?;
+        let re = format!("^{}/", regex_escape(rel.trim_end_matches('/')));
+
+        /*
+         * The expression should be enough, but check the paths as well in
+         * case the database returns a link we did not expect.
+         */
+        let mut links = self.collect_common(Some(&re), None, None)?.links;
+        links.retain(|l| l.path.starts_with(&dir));
+        Ok(links)
+    }
+
+    /*
+     * Return a path (e.g., "/dev" or "/devices") within the root directory of
+     * this handle.
+     */
+    fn rooted(&self, p: &str) -> PathBuf {
+        rooted(self.root.as_deref(), Path::new(p))
+    }
+
+    /*
+     * Interpret the path of a link (e.g., "/dev/dsk/c1t0d0s0", or just
+     * "dsk/c1t0d0s0") as a path in the /dev directory of this handle,
+     * returning the full path and the path relative to that directory.  For a
+     * handle with an alternate root, a "/dev" path may be given either with
+     * or without the root.
+     */
+    fn dev_path(&self, p: &Path) -> Result<(PathBuf, String)> {
+        let dev = self.rooted(DEV_ROOT);
+        let full = match p.strip_prefix(DEV_ROOT) {
+            Ok(rel) if !p.starts_with(&dev) => dev.join(rel),
+            _ => dev.join(p),
+        };
+
+        let Ok(rel) = full.strip_prefix(&dev) else {
+            bail!("{} is not within {}", full.display(), dev.display());
+        };
+        let Some(rel) = rel.to_str() else {
+            bail!("{} is not valid UTF-8", full.display());
+        };
+        Ok((full.clone(), rel.to_string()))
+    }
+
+    /**
+     * Return every link in the /dev namespace, for all minor nodes.
+     */
+    pub fn all_links(&self) -> Result<Vec<DevLink>> {
+        Ok(self.collect_common(None, None, None)?.links)
+    }
+
+    /**
+     * Wait for a particular /dev link (e.g., "/dev/dsk/c1t0d0s0", or just
+     * "dsk/c1t0d0s0") to exist and refer to a minor node that is present in
+     * the file system.  This is useful just after a device attaches, when
+     * devfsadm may not yet have created its links.  A new handle is created
+     * periodically, so that links created after the wait begins are seen,
+     * until the link is found or the timeout expires.
+     */
+    pub fn wait_for_link<P: AsRef<Path>>(
+        p: P,
+        timeout: Duration,
+    ) -> Result<DevLink> {
+        let link = Path::new(DEV_ROOT).join(p.as_ref());
+        let Ok(rel) = link.strip_prefix(DEV_ROOT) else {
+            bail!("{} is not within {}", link.display(), DEV_ROOT);
+        };
+        let Some(rel) = rel.to_str() else {
+            bail!("{} is not valid UTF-8", link.display());
+        };
+        let re = format!("^{}$", regex_escape(rel));
+
+        Self::wait_common(&re, &link.display().to_string(), timeout)
+    }
+
+    /**
+     * Wait for any /dev link whose path, relative to /dev, matches the
+     * provided regular expression (as with links_matching()) to exist and
+     * refer to a minor node that is present in the file system, as with
+     * wait_for_link().  If several links match, the first one found is
+     * returned.
+     */
+    pub fn wait_for_link_matching(
+        pattern: &str,
+        timeout: Duration,
+    ) -> Result<DevLink> {
+        Self::wait_common(
+            pattern,
+            &format!("link matching {:?}", pattern),
+            timeout,
+        )
+    }
+
+    fn wait_common(re: &str, what: &str, timeout: Duration) -> Result<DevLink> {
+        check_platform()?;
+        let start = Instant::now();
+
+        loop {
+            let why = match DevLinks::new(false) {
+                Ok(dl) => {
+                    let links = dl.collect_common(Some(re), None, None)?.links;
+                    if links.is_empty() {
+                        "not found".to_string()
+                    } else {
+                        let mut why = "link not yet created".to_string();
+                        for l in links {
+                            if !l.exists() {
+                                continue;
+                            }
+                            if !l.is_dangling()? {
+                                return Ok(l);
+                            }
+                            why = format!(
+                                "target {} missing",
+                                l.canonical_target().display(),
+                            );
+                        }
+                        why
+                    }
+                }
+                /*
+                 * The database is likely busy because devfsadm is updating
+                 * it, so try again.
+                 */
+                Err(e) if e.is::<DevLinksBusy>() => e.to_string(),
+                Err(e) => return Err(e),
+            };
+
+            if start.elapsed() >= timeout {
+                bail!("{} did not appear within {:?} ({})", what, timeout, why);
+            }
+
+            std::thread::sleep(
+                DEVICE_POLL_INTERVAL
+                    .min(timeout.saturating_sub(start.elapsed())),
+            );
+        }
+    }
+}
+
+const DEVLINK_RETRY_MIN: Duration = Duration::from_millis(10);
+const DEVLINK_RETRY_MAX: Duration = Duration::from_secs(1);
+const DEVLINK_MAX_DEPTH: usize = 8;
+const DEV_ROOT: &str = "/dev";
+
+/**
+ * The error returned when a devlink handle cannot be created because the
+ * devlink database is busy.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DevLinksBusy {
+    /**
+     * How long we waited for the database before giving up.
+     */
+    pub waited: Duration,
+}
+
+impl std::fmt::Display for DevLinksBusy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "di_devlink_init: devlink database busy")?;
+        if !self.waited.is_zero() {
+            write!(f, " (waited {:?})", self.waited)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for DevLinksBusy {}
+
+/*
+ * Escape any characters in a string that are special in a POSIX extended
+ * regular expression, so that it matches only itself.
+ */
+fn regex_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if "\\^$.|?*+()[]{}".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
     }
+    out
 }
 
+/*
+ * Return an absolute path within an alternate root directory, if there is
+ * one.
+ */
+fn rooted(root: Option<&Path>, p: &Path) -> PathBuf {
+    match root {
+        Some(root) => root.join(p.strip_prefix("/").unwrap_or(p)),
+        None => p.to_path_buf(),
+    }
+}
+
+/*
+ * Determine the path to which a link refers, given its content.  Relative
+ * content is interpreted with respect to the directory that contains the
+ * link, and any "." or ".." components are removed without reference to the
+ * file system.
+ */
+pub(crate) fn link_target_path(link: &Path, content: &Path) -> PathBuf {
+    let full = match link.parent() {
+        Some(dir) => dir.join(content),
+        None => content.to_path_buf(),
+    };
+
+    let mut out = PathBuf::new();
+    for c in full.components() {
+        match c {
+            std::path::Component::CurDir => (),
+            std::path::Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+pub struct DevLinkIter<'a> {
+    links: &'a DevLinks,
+    mpath: PathBuf,
+    buf: VecDeque<DevLink>,
+    done: bool,
+    invalid: Vec<InvalidDevLink>,
+}
+
+impl DevLinkIter<'_> {
+    /**
+     * Return the entries for the path that could not be decoded, and so were
+     * skipped.  This is empty until the iterator has first been advanced.
+     */
+    pub fn invalid(&self) -> &[InvalidDevLink] {
+        &self.invalid
+    }
+}
+
+impl Iterator for DevLinkIter<'_> {
+    type Item = Result<DevLink>;
+
+    fn next(&mut self) -> Option<Result<DevLink>> {
+        if !self.done {
+            self.done = true;
+            match self.links.collect_common(None, Some(&self.mpath), None) {
+                Ok(r) => {
+                    self.buf = r.links.into();
+                    self.invalid = r.invalid;
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        self.buf.pop_front().map(Ok)
+    }
+}
+
+/**
+ * Compose the options for a devlink handle, as returned by
+ * DevLinks::builder().  By default, no links are created and the handle is
+ * created without waiting if the devlink database is busy.
+ */
+#[derive(Clone, Debug, Default)]
+pub struct DevLinksBuilder {
+    name: Option<String>,
+    make_link: bool,
+    timeout: Option<Duration>,
+    #[cfg(feature = "private")]
+    root: Option<PathBuf>,
+}
+
+impl DevLinksBuilder {
+    /**
+     * Ask devfsadm to create any missing links (DI_MAKE_LINK) before the
+     * database is loaded.
+     */
+    pub fn make_link(mut self, make_link: bool) -> Self {
+        self.make_link = make_link;
+        self
+    }
+
+    /**
+     * When creating links, restrict devfsadm to the links for a particular
+     * driver (e.g., "sd") or minor path, rather than the whole system.
+     */
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /**
+     * If the devlink database is busy, keep trying until the timeout
+     * expires; see DevLinks::new_with_timeout().
+     */
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /**
+     * Use the devlink database of an alternate root directory; see
+     * DevLinks::new_with_root().
+     */
+    #[cfg(feature = "private")]
+    pub fn root<P: AsRef<Path>>(mut self, root: P) -> Self {
+        self.root = Some(root.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn build(self) -> Result<DevLinks> {
+        #[cfg(feature = "private")]
+        if let Some(root) = &self.root {
+            if self.make_link || self.name.is_some() {
+                bail!("links cannot be created in an alternate root");
+            }
+            return DevLinks::open_common(root);
+        }
+
+        let name = self.name.as_deref();
+        if let Some(timeout) = self.timeout {
+            DevLinks::new_retry(name, self.make_link, timeout)
+        } else {
+            DevLinks::new_common(name, self.make_link)
+        }
+    }
+}
+
+impl std::fmt::Debug for DevLinks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DevLinks")
+            .field("handle", &self.handle)
+            .field("root", &self.root)
+            .finish()
+    }
+}
+
+/*
+ * A devlink handle may be used from any thread, but walks update state
+ * within the handle, so it must not be used from more than one thread at a
+ * time.
+ */
+unsafe impl Send for DevLinks {}
+
 impl Drop for DevLinks {
     fn drop(&mut self) {
+        #[cfg(feature = "private")]
+        if self.root.is_some() {
+            assert_eq!(unsafe { di_devlink_close(&mut self.handle, 0) }, 0);
+            return;
+        }
+
         assert_eq!(unsafe { di_devlink_fini(&mut self.handle) }, 0);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn link(path: &str, content: &str) -> DevLink {
+        DevLink {
+            path: PathBuf::from(path),
+            content: PathBuf::from(content),
+            linktype: DevLinkType::Primary,
+            root: None,
+        }
+    }
+
+    #[test]
+    fn link_target() {
+        for (path, content, target) in [
+            (
+                "/dev/dsk/c1t0d0s0",
+                "../../devices/pci@0,0/pci1022,1483@1,1/disk@0,0:a",
+                "/devices/pci@0,0/pci1022,1483@1,1/disk@0,0:a",
+            ),
+            (
+                "/dev/null",
+                "../devices/pseudo/mm@0:null",
+                "/devices/pseudo/mm@0:null",
+            ),
+            ("/dev/term/a", "./../cua/./a", "/dev/cua/a"),
+            ("/dev/stdin", "/proc/self/fd/0", "/proc/self/fd/0"),
+            ("/dev/x", "../../../../y", "/y"),
+            ("x", "y", "y"),
+        ] {
+            assert_eq!(
+                link_target_path(Path::new(path), Path::new(content)),
+                Path::new(target),
+                "{} -> {}",
+                path,
+                content,
+            );
+        }
+    }
+
+    #[test]
+    fn canonical_target() {
+        for (path, content, target) in [
+            (
+                "/dev/rdsk/c1t0d0s0",
+                "../../devices/pci@0,0/disk@0,0:a,raw",
+                "/devices/pci@0,0/disk@0,0:a,raw",
+            ),
+            (
+                "/dev/dsk/c2t0014EE8401D04A00d0",
+                "../../devices/pci@0,0/pci1022,1483@1,2/pci1b96,0@0/\
+                 blkdev@w0014EE8401D04A00,0:wd",
+                "/devices/pci@0,0/pci1022,1483@1,2/pci1b96,0@0/\
+                 blkdev@w0014EE8401D04A00,0:wd",
+            ),
+            (
+                "/dev/zvol/dsk/rpool/swap",
+                "../../../../devices/pseudo/zfs@0:1c",
+                "/devices/pseudo/zfs@0:1c",
+            ),
+            (
+                "/dev/msglog",
+                "../devices/pseudo/sysmsg@0:msglog",
+                "/devices/pseudo/sysmsg@0:msglog",
+            ),
+            ("/dev/stdin", "./fd/0", "/dev/fd/0"),
+            /*
+             * Absolute content is returned as it is, even if it is not
+             * within /dev or /devices.
+             */
+            ("/dev/stdin", "/proc/self/fd/0", "/proc/self/fd/0"),
+        ] {
+            assert_eq!(
+                link(path, content).canonical_target(),
+                Path::new(target)
+            );
+        }
+    }
+
+    #[test]
+    fn canonical_target_root() {
+        for (path, content, target) in [
+            (
+                "/a/dev/dsk/c1t0d0s0",
+                "../../devices/pci@0,0/disk@0,0:a",
+                "/a/devices/pci@0,0/disk@0,0:a",
+            ),
+            (
+                "/a/dev/dsk/c1t0d0s0",
+                "/devices/pci@0,0/disk@0,0:a",
+                "/a/devices/pci@0,0/disk@0,0:a",
+            ),
+            ("/a/dev/fd0", "diskette0", "/a/dev/diskette0"),
+            ("/a/dev/stdin", "./fd/0", "/a/dev/fd/0"),
+            /*
+             * Absolute content that is not within /dev or /devices is still
+             * within the root, and is not treated as a devfs path.
+             */
+            ("/a/dev/stdin", "/proc/self/fd/0", "/a/proc/self/fd/0"),
+            ("/a/dev/x", "/tmp/x", "/a/tmp/x"),
+        ] {
+            let l = DevLink {
+                root: Some(PathBuf::from("/a")),
+                ..link(path, content)
+            };
+            assert_eq!(l.canonical_target(), Path::new(target));
+        }
+    }
+
+    #[test]
+    fn escape() {
+        assert_eq!(regex_escape("dsk/c1t0d0s0"), "dsk/c1t0d0s0");
+        assert_eq!(regex_escape("a.b*c[0]"), "a\\.b\\*c\\[0\\]");
+    }
+}
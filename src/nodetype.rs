@@ -0,0 +1,265 @@
+/*
+ * Copyright 2024 Oxide Computer Company
+ */
+
+/*
+ * Minor node types from <sys/sunddi.h>.  A node type is made up of a family
+ * (e.g., "ddi_block") optionally followed by one or more subtypes, each
+ * separated by a colon (e.g., "ddi_block:cdrom:channel").
+ */
+pub const DDI_NT_SERIAL: &str = "ddi_serial";
+pub const DDI_NT_SERIAL_MB: &str = "ddi_serial:mb";
+pub const DDI_NT_SERIAL_DO: &str = "ddi_serial:dialout";
+pub const DDI_NT_SERIAL_MB_DO: &str = "ddi_serial:dialout,mb";
+pub const DDI_NT_SERIAL_LOMCON: &str = "ddi_serial:lomcon";
+pub const DDI_NT_BLOCK: &str = "ddi_block";
+pub const DDI_NT_BLOCK_CHAN: &str = "ddi_block:channel";
+pub const DDI_NT_BLOCK_WWN: &str = "ddi_block:wwn";
+pub const DDI_NT_BLOCK_SAS: &str = "ddi_block:sas";
+pub const DDI_NT_BLOCK_BLKDEV: &str = "ddi_block:blkdev";
+pub const DDI_NT_BLOCK_FABRIC: &str = "ddi_block:fabric";
+pub const DDI_NT_CD: &str = "ddi_block:cdrom";
+pub const DDI_NT_CD_CHAN: &str = "ddi_block:cdrom:channel";
+pub const DDI_NT_FD: &str = "ddi_block:diskette";
+pub const DDI_NT_ENCLOSURE: &str = "ddi_enclosure";
+pub const DDI_NT_TAPE: &str = "ddi_byte:tape";
+pub const DDI_NT_NET: &str = "ddi_network";
+pub const DDI_NT_NET_WIFI: &str = "ddi_network:wifi";
+pub const DDI_NT_DISPLAY: &str = "ddi_display";
+pub const DDI_NT_DISPLAY_DRM: &str = "ddi_display:drm";
+pub const DDI_PSEUDO: &str = "ddi_pseudo";
+pub const DDI_NT_AUDIO: &str = "ddi_audio";
+pub const DDI_NT_MOUSE: &str = "ddi_mouse";
+pub const DDI_NT_KEYBOARD: &str = "ddi_keyboard";
+pub const DDI_NT_PARALLEL: &str = "ddi_parallel";
+pub const DDI_NT_PRINTER: &str = "ddi_printer";
+pub const DDI_NT_UGEN: &str = "ddi_generic:usb";
+pub const DDI_NT_NEXUS: &str = "ddi_ctl:devctl";
+pub const DDI_NT_SCSI_NEXUS: &str = "ddi_ctl:devctl:scsi";
+pub const DDI_NT_SATA_NEXUS: &str = "ddi_ctl:devctl:sata";
+pub const DDI_NT_NVME_NEXUS: &str = "ddi_ctl:devctl:nvme";
+pub const DDI_NT_ATTACHMENT_POINT: &str = "ddi_ctl:attachment_point";
+pub const DDI_NT_SCSI_ATTACHMENT_POINT: &str = "ddi_ctl:attachment_point:scsi";
+pub const DDI_NT_SATA_ATTACHMENT_POINT: &str = "ddi_ctl:attachment_point:sata";
+pub const DDI_NT_PCI_ATTACHMENT_POINT: &str = "ddi_ctl:attachment_point:pci";
+pub const DDI_NT_USB_ATTACHMENT_POINT: &str = "ddi_ctl:attachment_point:usb";
+pub const DDI_NT_NVME_ATTACHMENT_POINT: &str = "ddi_ctl:attachment_point:nvme";
+pub const DDI_NT_SMARTCARD_READER: &str = "ddi_smartcard_reader";
+
+/**
+ * The family of a minor node type; i.e., the portion before the first colon.
+ */
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MinorNodeFamily {
+    Block,
+    Byte,
+    Serial,
+    Network,
+    Display,
+    Pseudo,
+    Audio,
+    Mouse,
+    Keyboard,
+    Parallel,
+    Printer,
+    Generic,
+    Control,
+    Enclosure,
+    Sensor,
+    SmartcardReader,
+    Other(String),
+}
+
+impl MinorNodeFamily {
+    pub fn as_str(&self) -> &str {
+        match self {
+            MinorNodeFamily::Block => "ddi_block",
+            MinorNodeFamily::Byte => "ddi_byte",
+            MinorNodeFamily::Serial => "ddi_serial",
+            MinorNodeFamily::Network => "ddi_network",
+            MinorNodeFamily::Display => "ddi_display",
+            MinorNodeFamily::Pseudo => "ddi_pseudo",
+            MinorNodeFamily::Audio => "ddi_audio",
+            MinorNodeFamily::Mouse => "ddi_mouse",
+            MinorNodeFamily::Keyboard => "ddi_keyboard",
+            MinorNodeFamily::Parallel => "ddi_parallel",
+            MinorNodeFamily::Printer => "ddi_printer",
+            MinorNodeFamily::Generic => "ddi_generic",
+            MinorNodeFamily::Control => "ddi_ctl",
+            MinorNodeFamily::Enclosure => "ddi_enclosure",
+            MinorNodeFamily::Sensor => "ddi_sensor",
+            MinorNodeFamily::SmartcardReader => "ddi_smartcard_reader",
+            MinorNodeFamily::Other(s) => s,
+        }
+    }
+}
+
+impl From<&str> for MinorNodeFamily {
+    fn from(s: &str) -> Self {
+        match s {
+            "ddi_block" => MinorNodeFamily::Block,
+            "ddi_byte" => MinorNodeFamily::Byte,
+            "ddi_serial" => MinorNodeFamily::Serial,
+            "ddi_network" => MinorNodeFamily::Network,
+            "ddi_display" => MinorNodeFamily::Display,
+            "ddi_pseudo" => MinorNodeFamily::Pseudo,
+            "ddi_audio" => MinorNodeFamily::Audio,
+            "ddi_mouse" => MinorNodeFamily::Mouse,
+            "ddi_keyboard" => MinorNodeFamily::Keyboard,
+            "ddi_parallel" => MinorNodeFamily::Parallel,
+            "ddi_printer" => MinorNodeFamily::Printer,
+            "ddi_generic" => MinorNodeFamily::Generic,
+            "ddi_ctl" => MinorNodeFamily::Control,
+            "ddi_enclosure" => MinorNodeFamily::Enclosure,
+            "ddi_sensor" => MinorNodeFamily::Sensor,
+            "ddi_smartcard_reader" => MinorNodeFamily::SmartcardReader,
+            other => MinorNodeFamily::Other(other.to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for MinorNodeFamily {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/**
+ * A parsed minor node type, as returned by Minor::node_type_parsed().
+ */
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MinorNodeType {
+    family: MinorNodeFamily,
+    subtype: Option<String>,
+}
+
+impl MinorNodeType {
+    pub fn family(&self) -> &MinorNodeFamily {
+        &self.family
+    }
+
+    /**
+     * The portion of the node type after the family, if any; e.g.,
+     * "cdrom:channel" for "ddi_block:cdrom:channel".
+     */
+    pub fn subtype(&self) -> Option<&str> {
+        self.subtype.as_deref()
+    }
+
+    /**
+     * The individual colon-separated subtypes; e.g., ["cdrom", "channel"]
+     * for "ddi_block:cdrom:channel".
+     */
+    pub fn subtypes(&self) -> impl Iterator<Item = &str> {
+        self.subtype.as_deref().into_iter().flat_map(|s| s.split(':'))
+    }
+
+    /**
+     * Returns true if this node type is the same as, or a more specific
+     * subtype of, the provided node type; e.g., "ddi_block:cdrom:channel" is
+     * a "ddi_block:cdrom", and is also a "ddi_block".
+     */
+    pub fn is_a(&self, other: &str) -> bool {
+        let (family, subtype) = match other.split_once(':') {
+            Some((family, subtype)) => (family, Some(subtype)),
+            None => (other, None),
+        };
+        if self.family.as_str() != family {
+            return false;
+        }
+
+        let Some(subtype) = subtype else {
+            return true;
+        };
+        match self.subtype.as_deref().and_then(|s| s.strip_prefix(subtype)) {
+            Some(rest) => rest.is_empty() || rest.starts_with(':'),
+            None => false,
+        }
+    }
+}
+
+impl From<&str> for MinorNodeType {
+    fn from(s: &str) -> Self {
+        let (family, subtype) = match s.split_once(':') {
+            Some((family, subtype)) => (family, Some(subtype.to_string())),
+            None => (s, None),
+        };
+
+        MinorNodeType { family: family.into(), subtype }
+    }
+}
+
+impl std::fmt::Display for MinorNodeType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(subtype) = &self.subtype {
+            write!(f, "{}:{}", self.family, subtype)
+        } else {
+            write!(f, "{}", self.family)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let t = MinorNodeType::from(DDI_NT_BLOCK);
+        assert_eq!(t.family(), &MinorNodeFamily::Block);
+        assert_eq!(t.subtype(), None);
+        assert_eq!(t.subtypes().count(), 0);
+
+        let t = MinorNodeType::from(DDI_NT_BLOCK_SAS);
+        assert_eq!(t.family(), &MinorNodeFamily::Block);
+        assert_eq!(t.subtype(), Some("sas"));
+
+        let t = MinorNodeType::from(DDI_NT_CD_CHAN);
+        assert_eq!(t.subtype(), Some("cdrom:channel"));
+        assert_eq!(t.subtypes().collect::<Vec<_>>(), ["cdrom", "channel"]);
+
+        /*
+         * A family we do not know is kept as it is.
+         */
+        let t = MinorNodeType::from("ddi_blockx:sas");
+        assert_eq!(t.family(), &MinorNodeFamily::Other("ddi_blockx".into()));
+        assert_eq!(t.family().as_str(), "ddi_blockx");
+
+        for s in [DDI_NT_BLOCK, DDI_NT_CD_CHAN, "ddi_blockx:sas", "x"] {
+            assert_eq!(MinorNodeType::from(s).to_string(), s);
+        }
+    }
+
+    #[test]
+    fn is_a() {
+        let block = MinorNodeType::from(DDI_NT_BLOCK);
+        assert!(block.is_a(DDI_NT_BLOCK));
+        assert!(!block.is_a(DDI_NT_BLOCK_SAS));
+
+        let sas = MinorNodeType::from(DDI_NT_BLOCK_SAS);
+        assert!(sas.is_a(DDI_NT_BLOCK));
+        assert!(sas.is_a(DDI_NT_BLOCK_SAS));
+        assert!(!sas.is_a(DDI_NT_BLOCK_FABRIC));
+        assert!(!sas.is_a("ddi_block:sa"));
+
+        let cd = MinorNodeType::from(DDI_NT_CD_CHAN);
+        assert!(cd.is_a(DDI_NT_BLOCK));
+        assert!(cd.is_a(DDI_NT_CD));
+        assert!(cd.is_a(DDI_NT_CD_CHAN));
+        assert!(!cd.is_a(DDI_NT_BLOCK_CHAN));
+
+        /*
+         * A family is only matched as a whole.
+         */
+        let blockx = MinorNodeType::from("ddi_blockx");
+        assert!(!blockx.is_a(DDI_NT_BLOCK));
+        assert!(!block.is_a("ddi_blockx"));
+        assert!(!block.is_a("ddi_bloc"));
+
+        let other = MinorNodeType::from("ddi_widget:foo");
+        assert!(other.is_a("ddi_widget"));
+        assert!(other.is_a("ddi_widget:foo"));
+        assert!(!other.is_a(DDI_NT_BLOCK));
+        assert!(!block.is_a("ddi_widget"));
+    }
+}
@@ -0,0 +1,222 @@
+/*
+ * Copyright 2024 Oxide Computer Company
+ */
+
+/*
+ * NVMe controllers, as presented by nvme(4D).  Each controller node has a
+ * blkdev(4D) child for each attached namespace, and it is the blkdev node
+ * that has the disk minor nodes (and thus the cXtYdZ name).
+ */
+
+use crate::{DevInfo, DevLinks, Disk, DiskName, Node, TreeNode};
+use anyhow::Result;
+
+impl DevInfo {
+    /**
+     * Collect every NVMe controller in the snapshot.
+     */
+    pub fn nvme_controllers(&self) -> Result<Vec<NvmeController<'_>>> {
+        self.walk_driver("nvme")
+            .map(|n| Ok(NvmeController { node: n? }))
+            .collect()
+    }
+
+    /**
+     * Summarise every NVMe controller in the snapshot, along with the disk
+     * name of each of its namespaces; e.g., to map "nvme0" to "c1t...d0".
+     */
+    pub fn nvme_info(&self, links: &DevLinks) -> Result<Vec<NvmeInfo>> {
+        self.nvme_controllers()?.iter().map(|c| c.info(links)).collect()
+    }
+}
+
+/**
+ * An NVMe controller node.
+ */
+#[derive(Clone)]
+pub struct NvmeController<'a> {
+    node: Node<'a>,
+}
+
+impl<'a> NvmeController<'a> {
+    pub fn node(&self) -> &Node<'a> {
+        &self.node
+    }
+
+    /**
+     * The name of the controller, as used by nvmeadm(8); e.g., "nvme0".
+     */
+    pub fn name(&self) -> Option<String> {
+        Some(format!("nvme{}", self.node.instance()?))
+    }
+
+    /**
+     * The namespaces of this controller that are attached to blkdev(4D).
+     * Namespaces that are not attached (e.g., because they are inactive, or
+     * have been detached with nvmeadm(8)) have no node, and are not
+     * included.
+     */
+    pub fn namespaces(&self) -> Result<Vec<NvmeNamespace<'a>>> {
+        let mut out = Vec::new();
+        for c in self.node.children() {
+            if c.driver_name().as_deref() != Some("blkdev")
+                && c.node_name() != "blkdev"
+            {
+                continue;
+            }
+            if let Some(disk) = Disk::from_node(c)? {
+                out.push(NvmeNamespace { disk });
+            }
+        }
+        out.sort_by_key(|ns| ns.nsid());
+        Ok(out)
+    }
+
+    /*
+     * The identify controller data is not part of the device tree, but
+     * blkdev(4D) publishes the model, serial number, and firmware revision
+     * on each namespace as inquiry properties, so look there if the
+     * controller does not have the property itself.
+     */
+    fn prop_str(&self, name: &str, inquiry: &str) -> Result<Option<String>> {
+        if let Some(v) = self.node.prop_str(name) {
+            return Ok(Some(v.trim().to_string()));
+        }
+        Ok(self
+            .namespaces()?
+            .iter()
+            .find_map(|ns| ns.disk.node().prop_str(inquiry))
+            .map(|v| v.trim().to_string()))
+    }
+
+    pub fn model(&self) -> Result<Option<String>> {
+        self.prop_str("model-number", "inquiry-product-id")
+    }
+
+    pub fn serial(&self) -> Result<Option<String>> {
+        self.prop_str("serial-number", "inquiry-serial-no")
+    }
+
+    pub fn firmware(&self) -> Result<Option<String>> {
+        self.prop_str("firmware-version", "inquiry-revision-id")
+    }
+
+    pub fn info(&self, links: &DevLinks) -> Result<NvmeInfo> {
+        let mut namespaces = Vec::new();
+        for ns in self.namespaces()? {
+            namespaces.push(NvmeNamespaceInfo {
+                nsid: ns.nsid(),
+                eui64: ns.eui64(),
+                disk_name: ns.disk_name(links)?.map(|n| n.to_string()),
+                devfs_path: ns.disk.node().devfs_path()?,
+            });
+        }
+
+        Ok(NvmeInfo {
+            name: self.name(),
+            devfs_path: self.node.devfs_path()?,
+            model: self.model()?,
+            serial: self.serial()?,
+            firmware: self.firmware()?,
+            namespaces,
+        })
+    }
+}
+
+impl std::fmt::Debug for NvmeController<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NvmeController")
+            .field("name", &self.name())
+            .field("devfs_path", &self.node.devfs_path().ok())
+            .finish()
+    }
+}
+
+/**
+ * An NVMe namespace that is attached to blkdev(4D).
+ */
+#[derive(Clone)]
+pub struct NvmeNamespace<'a> {
+    disk: Disk<'a>,
+}
+
+impl<'a> NvmeNamespace<'a> {
+    pub fn disk(&self) -> &Disk<'a> {
+        &self.disk
+    }
+
+    /*
+     * The unit address of the blkdev node is either "w<EUI64>,<lun>", if the
+     * namespace has an EUI64, or "<nsid>,<lun>", both in hexadecimal.
+     */
+    fn address(&self) -> Option<String> {
+        let a = self.disk.node().unit_address()?;
+        Some(a.split(',').next()?.to_string())
+    }
+
+    /**
+     * The namespace ID, if it can be determined from the unit address; it
+     * cannot for a namespace that has an EUI64.
+     */
+    pub fn nsid(&self) -> Option<u32> {
+        let a = self.address()?;
+        if a.starts_with('w') {
+            return None;
+        }
+        u32::from_str_radix(&a, 16).ok()
+    }
+
+    /**
+     * The IEEE Extended Unique Identifier of the namespace, as a string of
+     * hexadecimal digits, if it has one.
+     */
+    pub fn eui64(&self) -> Option<String> {
+        Some(self.address()?.strip_prefix('w')?.to_ascii_lowercase())
+    }
+
+    /**
+     * The public name of the disk for this namespace; e.g., "c1t0d0".
+     */
+    pub fn disk_name(&self, links: &DevLinks) -> Result<Option<DiskName>> {
+        self.disk.name(links)
+    }
+}
+
+impl std::fmt::Debug for NvmeNamespace<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NvmeNamespace")
+            .field("nsid", &self.nsid())
+            .field("eui64", &self.eui64())
+            .field("disk", &self.disk)
+            .finish()
+    }
+}
+
+/**
+ * A summary of an NVMe controller and its namespaces.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NvmeInfo {
+    /**
+     * The controller name; e.g., "nvme0".
+     */
+    pub name: Option<String>,
+    pub devfs_path: String,
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    pub firmware: Option<String>,
+    pub namespaces: Vec<NvmeNamespaceInfo>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NvmeNamespaceInfo {
+    pub nsid: Option<u32>,
+    pub eui64: Option<String>,
+    /**
+     * The public name of the disk; e.g., "c1t0d0".
+     */
+    pub disk_name: Option<String>,
+    pub devfs_path: String,
+}
@@ -0,0 +1,204 @@
+/*
+ * Copyright 2024 Oxide Computer Company
+ */
+
+use crate::sys::*;
+use crate::{DevInfo, Node, PropDevt, PropValue, SpecType};
+use anyhow::Result;
+use libc::dev_t;
+#[cfg(feature = "serde")]
+use std::path::Path;
+
+/**
+ * A copy of a device tree snapshot that does not depend on the libdevinfo
+ * handle from which it was taken.  The data can be cached, sent to other
+ * threads, or inspected after the original snapshot has been dropped.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedDevTree {
+    pub root: OwnedNode,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedNode {
+    pub node_name: String,
+    pub driver_name: Option<String>,
+    pub instance: Option<i32>,
+    pub unit_address: Option<String>,
+    pub devfs_path: String,
+    pub props: Vec<OwnedProperty>,
+    pub minors: Vec<OwnedMinor>,
+    pub children: Vec<OwnedNode>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedProperty {
+    pub name: String,
+    /**
+     * The minor node, if any, with which this property is associated.
+     */
+    pub devt: PropDevt,
+    pub value: PropValue,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedMinor {
+    pub name: String,
+    pub node_type: String,
+    /**
+     * The type of the minor node, or None if it is not one known to this
+     * crate.
+     */
+    pub spec_type: Option<SpecType>,
+    pub devt: dev_t,
+    pub devfs_path: String,
+}
+
+impl OwnedNode {
+    fn from_node(n: &Node) -> Result<OwnedNode> {
+        let props = n
+            .props()
+            .map(|p| {
+                let p = p?;
+                /*
+                 * A property that cannot be decoded according to its type
+                 * (e.g., because a driver published it with the wrong type)
+                 * is kept as raw data, rather than failing the whole copy.
+                 */
+                let value = p.value().unwrap_or_else(|_| {
+                    PropValue::Unknown(
+                        p.raw_bytes().map(<[u8]>::to_vec).unwrap_or_default(),
+                    )
+                });
+                Ok(OwnedProperty { name: p.name(), devt: p.devt(), value })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let minors = n
+            .minors()
+            .map(|m| {
+                let m = m?;
+                Ok(OwnedMinor {
+                    name: m.name(),
+                    node_type: m.node_type(),
+                    spec_type: m.try_spec_type(),
+                    devt: m.devt(),
+                    devfs_path: m.devfs_path()?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut children = Vec::new();
+        let mut child = unsafe { di_child_node(n.node) };
+        while child != DI_NODE_NIL {
+            children.push(OwnedNode::from_node(&Node {
+                parent: n.parent,
+                node: child,
+            })?);
+            child = unsafe { di_sibling_node(child) };
+        }
+
+        Ok(OwnedNode {
+            node_name: n.node_name(),
+            driver_name: n.driver_name(),
+            instance: n.instance(),
+            unit_address: n.unit_address(),
+            devfs_path: n.devfs_path()?,
+            props,
+            minors,
+            children,
+        })
+    }
+
+    /**
+     * Look up a property of this node by name, returning the first one
+     * found that is not associated with a particular minor node.
+     */
+    pub fn prop(&self, name: &str) -> Option<&PropValue> {
+        self.props
+            .iter()
+            .find(|p| p.devt.dev().is_none() && p.name == name)
+            .map(|p| &p.value)
+    }
+
+    /**
+     * Visit this node and all of its descendants, in depth-first order.
+     */
+    pub fn walk(&self) -> OwnedNodeWalk<'_> {
+        OwnedNodeWalk { stack: vec![self] }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl OwnedDevTree {
+    /**
+     * Write the snapshot to a file, as JSON, for later analysis with
+     * OwnedDevTree::load(); e.g., on another machine.
+     */
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let f = std::fs::File::create(path.as_ref())?;
+        self.to_writer(std::io::BufWriter::new(f))
+    }
+
+    /**
+     * Read a snapshot previously written by OwnedDevTree::save().
+     */
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<OwnedDevTree> {
+        let f = std::fs::File::open(path.as_ref())?;
+        Self::from_reader(std::io::BufReader::new(f))
+    }
+
+    pub fn to_writer<W: std::io::Write>(&self, w: W) -> Result<()> {
+        Ok(serde_json::to_writer(w, self)?)
+    }
+
+    pub fn from_reader<R: std::io::Read>(r: R) -> Result<OwnedDevTree> {
+        Ok(serde_json::from_reader(r)?)
+    }
+}
+
+impl OwnedDevTree {
+    /**
+     * Copy all of the nodes, minors, and properties from a snapshot.
+     */
+    pub fn from_devinfo(di: &DevInfo) -> Result<OwnedDevTree> {
+        let root = Node { parent: di, node: di.root };
+        Ok(OwnedDevTree { root: OwnedNode::from_node(&root)? })
+    }
+
+    /**
+     * Visit every node in the tree, in depth-first order.
+     */
+    pub fn walk(&self) -> OwnedNodeWalk<'_> {
+        self.root.walk()
+    }
+
+    /**
+     * Find the node with the given devfs path; e.g., "/pci@0,0".
+     */
+    pub fn find(&self, devfs_path: &str) -> Option<&OwnedNode> {
+        self.walk().find(|n| n.devfs_path == devfs_path)
+    }
+}
+
+pub struct OwnedNodeWalk<'a> {
+    stack: Vec<&'a OwnedNode>,
+}
+
+impl<'a> Iterator for OwnedNodeWalk<'a> {
+    type Item = &'a OwnedNode;
+
+    fn next(&mut self) -> Option<&'a OwnedNode> {
+        let n = self.stack.pop()?;
+        /*
+         * Push the children in reverse so that the first child is visited
+         * next.
+         */
+        self.stack.extend(n.children.iter().rev());
+        Some(n)
+    }
+}
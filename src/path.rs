@@ -0,0 +1,463 @@
+/*
+ * Copyright 2024 Oxide Computer Company
+ */
+
+use crate::sys::*;
+use crate::{decode_prop_value, prop_slice, prop_strings};
+use crate::{DevInfo, Node, PropType, PropValue, SnapshotFlags};
+use anyhow::{bail, Result};
+use num_enum::TryFromPrimitive;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_uchar};
+
+/**
+ * The state of a multipath path, from di_path_state(3DEVINFO).
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, TryFromPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(i32)]
+#[non_exhaustive]
+pub enum PathState {
+    Unknown = 0,
+    Offline = 1,
+    Standby = 2,
+    Online = 3,
+    Fault = 4,
+}
+
+impl std::fmt::Display for PathState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            PathState::Unknown => "unknown",
+            PathState::Offline => "offline",
+            PathState::Standby => "standby",
+            PathState::Online => "online",
+            PathState::Fault => "fault",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl DevInfo {
+    /**
+     * Take a snapshot of the whole tree that includes multipath (I/O path)
+     * information, as is required for Node::client_paths() and
+     * Node::phci_paths().
+     */
+    pub fn new_with_paths() -> Result<Self> {
+        DevInfo::builder().with(SnapshotFlags::PATH).build()
+    }
+}
+
+/*
+ * Which end of a path a walk begins from.
+ */
+#[derive(Clone, Copy)]
+enum PathEnd {
+    Client,
+    Phci,
+}
+
+impl<'a> Node<'a> {
+    /**
+     * Walk the paths to this node, if it is a multipath client device; e.g.,
+     * a disk under scsi_vhci(4D) control has one path for each HBA port
+     * (pHCI) through which it is visible.  The snapshot must have been taken
+     * with SnapshotFlags::PATH; otherwise, no paths are returned.
+     */
+    pub fn client_paths(&self) -> PathInfoWalk<'a> {
+        PathInfoWalk {
+            parent: self.parent,
+            node: self.node,
+            end: PathEnd::Client,
+            path: DI_PATH_NIL,
+            fin: false,
+        }
+    }
+
+    /**
+     * Returns true if this node is a multipath client device with at least
+     * one path; e.g., a disk under scsi_vhci(4D) control.  As with
+     * client_paths(), this is only accurate if the snapshot was taken with
+     * SnapshotFlags::PATH.
+     */
+    pub fn is_multipathed(&self) -> bool {
+        self.client_paths().next().is_some()
+    }
+
+    /**
+     * Walk the paths that pass through this node, if it is a pHCI (i.e., an
+     * HBA port participating in multipathing).  The snapshot must have been
+     * taken with SnapshotFlags::PATH; otherwise, no paths are returned.
+     */
+    pub fn phci_paths(&self) -> PathInfoWalk<'a> {
+        PathInfoWalk {
+            parent: self.parent,
+            node: self.node,
+            end: PathEnd::Phci,
+            path: DI_PATH_NIL,
+            fin: false,
+        }
+    }
+}
+
+/**
+ * One path to a multipath client device, as returned by
+ * Node::multipath_phcis(); this is the information presented for each path by
+ * mpathadm(8).
+ */
+#[derive(Clone)]
+pub struct PhciPath<'a> {
+    /**
+     * The pHCI (i.e., initiator port) through which the client is reached.
+     */
+    pub phci: Node<'a>,
+    /**
+     * The unit address of the client on this path; e.g.,
+     * "w5000c500a6a3b2f1,0".
+     */
+    pub address: Option<String>,
+    /**
+     * The target port on this path, if the pHCI driver provides one; e.g.,
+     * "w5000c500a6a3b2f1".
+     */
+    pub target_port: Option<String>,
+    pub state: PathState,
+}
+
+impl<'a> Node<'a> {
+    /**
+     * For a multipath client device (e.g., a disk under scsi_vhci(4D)
+     * control), list the pHCI nodes through which it can be reached, along
+     * with the address and state of each path.
+     */
+    pub fn multipath_phcis(&self) -> Result<Vec<PhciPath<'a>>> {
+        let mut out = Vec::new();
+        for p in self.client_paths() {
+            let p = p?;
+            let Some(phci) = p.phci() else {
+                continue;
+            };
+
+            out.push(PhciPath {
+                phci,
+                address: p.bus_addr(),
+                target_port: p.prop_str("target-port"),
+                state: p.state(),
+            });
+        }
+        Ok(out)
+    }
+
+    /**
+     * For a pHCI (e.g., an HBA port), list the multipath client devices
+     * reachable through it.  Each client is listed once, even if there is
+     * more than one path to it through this pHCI.
+     */
+    pub fn multipath_clients(&self) -> Result<Vec<Node<'a>>> {
+        let mut out: Vec<Node<'a>> = Vec::new();
+        for p in self.phci_paths() {
+            let Some(client) = p?.client() else {
+                continue;
+            };
+
+            if !out.iter().any(|n| n.node == client.node) {
+                out.push(client);
+            }
+        }
+        Ok(out)
+    }
+}
+
+pub struct PathInfoWalk<'p> {
+    parent: &'p DevInfo,
+    node: *mut di_node_t,
+    end: PathEnd,
+    path: *mut di_path_t,
+    fin: bool,
+}
+
+impl<'a> Iterator for PathInfoWalk<'a> {
+    type Item = Result<PathInfo<'a>>;
+
+    fn next(&mut self) -> Option<Result<PathInfo<'a>>> {
+        if self.fin {
+            return None;
+        }
+
+        self.path = match self.end {
+            PathEnd::Client => unsafe {
+                di_path_client_next_path(self.node, self.path)
+            },
+            PathEnd::Phci => unsafe {
+                di_path_phci_next_path(self.node, self.path)
+            },
+        };
+        if self.path == DI_PATH_NIL {
+            self.fin = true;
+            return None;
+        }
+
+        Some(Ok(PathInfo { parent: self.parent, path: self.path }))
+    }
+}
+
+/**
+ * A path-info node, which describes one path between a multipath client
+ * device and a pHCI through which it can be reached.
+ */
+pub struct PathInfo<'p> {
+    parent: &'p DevInfo,
+    path: *mut di_path_t,
+}
+
+fn path_str(p: *const c_char) -> Option<String> {
+    if p.is_null() {
+        None
+    } else {
+        Some(unsafe { CStr::from_ptr(p) }.to_string_lossy().to_string())
+    }
+}
+
+impl<'a> PathInfo<'a> {
+    /**
+     * The node name of the client device on this path; e.g., "disk".
+     */
+    pub fn node_name(&self) -> Option<String> {
+        path_str(unsafe { di_path_node_name(self.path) })
+    }
+
+    /**
+     * The unit address of the client device as seen through the pHCI on
+     * this path; e.g., "w5000c500a6a3b2f1,0".
+     */
+    pub fn bus_addr(&self) -> Option<String> {
+        path_str(unsafe { di_path_bus_addr(self.path) })
+    }
+
+    pub fn instance(&self) -> i32 {
+        unsafe { di_path_instance(self.path) }
+    }
+
+    /**
+     * The state of the path.  Any state not known to this crate is reported
+     * as PathState::Unknown.
+     */
+    pub fn state(&self) -> PathState {
+        PathState::try_from(self.state_raw()).unwrap_or(PathState::Unknown)
+    }
+
+    pub fn state_raw(&self) -> i32 {
+        unsafe { di_path_state(self.path) }
+    }
+
+    /**
+     * Walk the properties of this path; e.g., "target-port" and "lun".
+     */
+    pub fn props(&self) -> PathPropertyWalk<'a> {
+        PathPropertyWalk {
+            _parent: self.parent,
+            path: self.path,
+            prop: DI_PATH_PROP_NIL,
+            fin: false,
+        }
+    }
+
+    /**
+     * Look up an integer path property by name, returning the first value.
+     */
+    pub fn prop_i32(&self, name: &str) -> Option<i32> {
+        let name = CString::new(name).ok()?;
+        let mut data: *mut c_int = std::ptr::null_mut();
+        let n = unsafe {
+            di_path_prop_lookup_ints(self.path, name.as_ptr(), &mut data)
+        };
+        prop_slice(n, data)?.first().copied()
+    }
+
+    /**
+     * Look up a 64-bit integer path property by name, returning the first
+     * value.
+     */
+    pub fn prop_i64(&self, name: &str) -> Option<i64> {
+        let name = CString::new(name).ok()?;
+        let mut data: *mut i64 = std::ptr::null_mut();
+        let n = unsafe {
+            di_path_prop_lookup_int64s(self.path, name.as_ptr(), &mut data)
+        };
+        prop_slice(n, data)?.first().copied()
+    }
+
+    /**
+     * Look up a string path property by name, returning the first value;
+     * e.g., "target-port".
+     */
+    pub fn prop_str(&self, name: &str) -> Option<String> {
+        let name = CString::new(name).ok()?;
+        let mut data: *mut c_char = std::ptr::null_mut();
+        let n = unsafe {
+            di_path_prop_lookup_strings(self.path, name.as_ptr(), &mut data)
+        };
+        prop_strings(n, data)?.first()?.to_str().ok().map(str::to_string)
+    }
+
+    /**
+     * Look up a byte array path property by name.
+     */
+    pub fn prop_bytes(&self, name: &str) -> Option<&'a [u8]> {
+        let name = CString::new(name).ok()?;
+        let mut data: *mut c_uchar = std::ptr::null_mut();
+        let n = unsafe {
+            di_path_prop_lookup_bytes(self.path, name.as_ptr(), &mut data)
+        };
+        prop_slice(n, data)
+    }
+
+    /**
+     * The multipath client device; e.g., the scsi_vhci child node for a
+     * disk.  This is only present in the snapshot if the client is.
+     */
+    pub fn client(&self) -> Option<Node<'a>> {
+        let node = unsafe { di_path_client_node(self.path) };
+        (node != DI_NODE_NIL).then_some(Node { parent: self.parent, node })
+    }
+
+    /**
+     * The pHCI through which this path passes; e.g., an mpt_sas(4D) port.
+     * This is only present in the snapshot if the pHCI is.
+     */
+    pub fn phci(&self) -> Option<Node<'a>> {
+        let node = unsafe { di_path_phci_node(self.path) };
+        (node != DI_NODE_NIL).then_some(Node { parent: self.parent, node })
+    }
+}
+
+impl std::fmt::Debug for PathInfo<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PathInfo")
+            .field("node_name", &self.node_name())
+            .field("bus_addr", &self.bus_addr())
+            .field("instance", &self.instance())
+            .field("state", &self.state())
+            .finish()
+    }
+}
+
+pub struct PathPropertyWalk<'p> {
+    _parent: &'p DevInfo,
+    path: *mut di_path_t,
+    prop: *mut di_path_prop_t,
+    fin: bool,
+}
+
+impl<'a> Iterator for PathPropertyWalk<'a> {
+    type Item = Result<PathProperty<'a>>;
+
+    fn next(&mut self) -> Option<Result<PathProperty<'a>>> {
+        if self.fin {
+            return None;
+        }
+
+        self.prop = unsafe { di_path_prop_next(self.path, self.prop) };
+        if self.prop == DI_PATH_PROP_NIL {
+            self.fin = true;
+            return None;
+        }
+
+        Some(Ok(PathProperty { _parent: self._parent, prop: self.prop }))
+    }
+}
+
+pub struct PathProperty<'p> {
+    _parent: &'p DevInfo,
+    prop: *mut di_path_prop_t,
+}
+
+impl PathProperty<'_> {
+    pub fn name(&self) -> String {
+        unsafe { CStr::from_ptr(di_path_prop_name(self.prop)) }
+            .to_string_lossy()
+            .to_string()
+    }
+
+    pub fn value_type(&self) -> PropType {
+        PropType::try_from(unsafe { di_path_prop_type(self.prop) })
+            .unwrap_or(PropType::Unknown)
+    }
+
+    pub fn as_i32s(&self) -> Option<&[i32]> {
+        match self.value_type() {
+            PropType::Int32 => {
+                let mut data: *mut c_int = std::ptr::null_mut();
+                let n = unsafe { di_path_prop_ints(self.prop, &mut data) };
+                prop_slice(n, data)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_i64s(&self) -> Option<&[i64]> {
+        match self.value_type() {
+            PropType::Int64 => {
+                let mut data: *mut i64 = std::ptr::null_mut();
+                let n = unsafe { di_path_prop_int64s(self.prop, &mut data) };
+                prop_slice(n, data)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_cstrs(&self) -> Option<Vec<&CStr>> {
+        match self.value_type() {
+            PropType::String => {
+                let mut data: *mut c_char = std::ptr::null_mut();
+                let n = unsafe { di_path_prop_strings(self.prop, &mut data) };
+                prop_strings(n, data)
+            }
+            _ => None,
+        }
+    }
+
+    /**
+     * Return the property value as bytes, regardless of its declared type.
+     */
+    pub fn bytes(&self) -> Option<&[u8]> {
+        let mut data: *mut c_uchar = std::ptr::null_mut();
+        let n = unsafe { di_path_prop_bytes(self.prop, &mut data) };
+        prop_slice(n, data)
+    }
+
+    /**
+     * Decode the value of the property according to its type.
+     */
+    pub fn value(&self) -> Result<PropValue> {
+        let v = decode_prop_value(
+            self.value_type(),
+            || self.as_i32s(),
+            || self.as_i64s(),
+            || self.as_cstrs(),
+            || self.bytes(),
+        );
+
+        match v {
+            Some(v) => Ok(v),
+            None => {
+                let e = std::io::Error::last_os_error();
+                bail!(
+                    "could not decode path property {:?}: {}",
+                    self.name(),
+                    e
+                );
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for PathProperty<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PathProperty")
+            .field("name", &self.name())
+            .field("value", &self.value().ok())
+            .finish()
+    }
+}
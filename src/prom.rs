@@ -0,0 +1,196 @@
+/*
+ * Copyright 2024 Oxide Computer Company
+ */
+
+use crate::sys::*;
+use crate::{prop_slice, prop_strings, DevInfo, Node};
+use anyhow::{bail, Result};
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_uchar};
+
+/**
+ * A handle for access to firmware (PROM) properties, which are not included in
+ * a regular device tree snapshot.  PROM properties are retrieved for a
+ * particular node through Node::prom_props().
+ */
+pub struct Prom {
+    handle: *mut di_prom_handle_t,
+}
+
+impl Prom {
+    pub fn new() -> Result<Self> {
+        check_platform()?;
+        let handle = unsafe { di_prom_init() };
+        if handle == DI_PROM_HANDLE_NIL {
+            let e = std::io::Error::last_os_error();
+            bail!("di_prom_init: {}", e);
+        }
+
+        Ok(Prom { handle })
+    }
+}
+
+/*
+ * PROM properties are fetched and cached within the handle as they are
+ * requested, so it must not be used from more than one thread at a time.
+ */
+unsafe impl Send for Prom {}
+
+impl Drop for Prom {
+    fn drop(&mut self) {
+        unsafe { di_prom_fini(self.handle) };
+    }
+}
+
+impl std::fmt::Debug for Prom {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Prom").field("handle", &self.handle).finish()
+    }
+}
+
+impl<'a> Node<'a> {
+    pub fn prom_props<'p>(&self, prom: &'p Prom) -> PromPropertyWalk<'p>
+    where
+        'a: 'p,
+    {
+        PromPropertyWalk {
+            _parent: self.parent,
+            prom,
+            node: self.node,
+            prop: DI_PROM_PROP_NIL,
+            fin: false,
+        }
+    }
+
+    /**
+     * Look up an integer PROM property of this node by name, returning the
+     * first value.
+     */
+    pub fn prom_prop_i32(&self, prom: &Prom, name: &str) -> Option<i32> {
+        let name = CString::new(name).ok()?;
+        let mut data: *mut c_int = std::ptr::null_mut();
+        let n = unsafe {
+            di_prom_prop_lookup_ints(
+                prom.handle,
+                self.node,
+                name.as_ptr(),
+                &mut data,
+            )
+        };
+        prop_slice(n, data)?.first().copied()
+    }
+
+    /**
+     * Look up a string PROM property of this node by name, returning the
+     * first value; e.g., "bootpath" on the "/chosen" node.
+     */
+    pub fn prom_prop_str(&self, prom: &Prom, name: &str) -> Option<String> {
+        let name = CString::new(name).ok()?;
+        let mut data: *mut c_char = std::ptr::null_mut();
+        let n = unsafe {
+            di_prom_prop_lookup_strings(
+                prom.handle,
+                self.node,
+                name.as_ptr(),
+                &mut data,
+            )
+        };
+        prop_strings(n, data)?.first()?.to_str().ok().map(str::to_string)
+    }
+
+    /**
+     * Look up a PROM property of this node by name, returning the raw bytes.
+     */
+    pub fn prom_prop_bytes<'p>(
+        &self,
+        prom: &'p Prom,
+        name: &str,
+    ) -> Option<&'p [u8]> {
+        let name = CString::new(name).ok()?;
+        let mut data: *mut c_uchar = std::ptr::null_mut();
+        let n = unsafe {
+            di_prom_prop_lookup_bytes(
+                prom.handle,
+                self.node,
+                name.as_ptr(),
+                &mut data,
+            )
+        };
+        prop_slice(n, data)
+    }
+}
+
+pub struct PromPropertyWalk<'p> {
+    _parent: &'p DevInfo,
+    prom: &'p Prom,
+    node: *mut di_node_t,
+    prop: *mut di_prom_prop_t,
+    fin: bool,
+}
+
+impl<'a> Iterator for PromPropertyWalk<'a> {
+    type Item = Result<PromProperty<'a>>;
+
+    fn next(&mut self) -> Option<Result<PromProperty<'a>>> {
+        if self.fin {
+            return None;
+        }
+
+        self.prop = unsafe {
+            di_prom_prop_next(self.prom.handle, self.node, self.prop)
+        };
+        if self.prop == DI_PROM_PROP_NIL {
+            self.fin = true;
+            return None;
+        }
+
+        Some(Ok(PromProperty { _prom: self.prom, prop: self.prop }))
+    }
+}
+
+pub struct PromProperty<'p> {
+    _prom: &'p Prom,
+    prop: *mut di_prom_prop_t,
+}
+
+impl PromProperty<'_> {
+    pub fn name(&self) -> String {
+        unsafe { CStr::from_ptr(di_prom_prop_name(self.prop)) }
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /**
+     * PROM properties carry no type information; the value is returned as the
+     * raw bytes provided by firmware.  Boolean properties have no data.
+     */
+    pub fn data(&self) -> &[u8] {
+        let mut data: *mut c_uchar = std::ptr::null_mut();
+        let n = unsafe { di_prom_prop_data(self.prop, &mut data) };
+        if n <= 0 || data.is_null() {
+            &[]
+        } else {
+            unsafe { std::slice::from_raw_parts(data, n.try_into().unwrap()) }
+        }
+    }
+
+    /**
+     * Interpret the property value as a NUL-terminated string.
+     */
+    pub fn to_str(&self) -> Option<String> {
+        CStr::from_bytes_until_nul(self.data())
+            .ok()?
+            .to_str()
+            .ok()
+            .map(str::to_string)
+    }
+}
+
+impl std::fmt::Debug for PromProperty<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PromProperty")
+            .field("name", &self.name())
+            .field("data", &self.data())
+            .finish()
+    }
+}
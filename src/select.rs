@@ -0,0 +1,197 @@
+/*
+ * Copyright 2024 Oxide Computer Company
+ */
+
+/*
+ * Glob-style selection of nodes by devfs path, and optionally by driver name.
+ * Within a path component, an asterisk matches any run of characters and "?"
+ * matches any single character; neither matches a "/".  A component that is
+ * just two asterisks matches any number (including zero) of whole components.
+ * For example, the pattern "/pci@0,0/pci*" matches each of the bridges
+ * directly below "/pci@0,0", and "/pci@0,0/pci*" followed by "/disk@*" matches
+ * the disks attached to those bridges.
+ */
+
+use crate::{DevInfo, Node};
+use anyhow::{bail, Result};
+use std::str::FromStr;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Selector {
+    components: Vec<String>,
+    driver: Option<String>,
+}
+
+impl Selector {
+    /**
+     * Also require that the node be bound to a driver whose name matches the
+     * given pattern; e.g., "nvme" or "*sas".
+     */
+    pub fn driver(mut self, pattern: &str) -> Self {
+        self.driver = Some(pattern.to_string());
+        self
+    }
+
+    pub fn matches(&self, node: &Node) -> Result<bool> {
+        if let Some(driver) = &self.driver {
+            match node.driver_name() {
+                Some(name) if glob_match(driver, &name) => (),
+                _ => return Ok(false),
+            }
+        }
+
+        let path = node.devfs_path()?;
+        let components: Vec<&str> =
+            path.split('/').filter(|c| !c.is_empty()).collect();
+        Ok(self.matches_path(&components))
+    }
+
+    /**
+     * Check a devfs path, already split into components, against the
+     * pattern.  The driver pattern, if any, is not considered.
+     */
+    fn matches_path(&self, components: &[&str]) -> bool {
+        path_match(&self.components, components)
+    }
+}
+
+impl FromStr for Selector {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let Some(rest) = s.strip_prefix('/') else {
+            bail!("selector {:?} must begin with \"/\"", s);
+        };
+
+        let components = rest
+            .split('/')
+            .filter(|c| !c.is_empty())
+            .map(str::to_string)
+            .collect();
+
+        Ok(Selector { components, driver: None })
+    }
+}
+
+fn path_match(pattern: &[String], components: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => components.is_empty(),
+        Some((p, rest)) if p == "**" => {
+            (0..=components.len()).any(|i| path_match(rest, &components[i..]))
+        }
+        Some((p, rest)) => match components.split_first() {
+            Some((c, crest)) => glob_match(p, c) && path_match(rest, crest),
+            None => false,
+        },
+    }
+}
+
+fn glob_match(pattern: &str, s: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let s: Vec<char> = s.chars().collect();
+
+    /*
+     * Match iteratively, remembering the position of the most recent "*" so
+     * that we can backtrack and have it consume one more character.
+     */
+    let (mut pi, mut si) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while si < s.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == s[si]) {
+            pi += 1;
+            si += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, si));
+            pi += 1;
+        } else if let Some((spi, ssi)) = star {
+            pi = spi + 1;
+            si = ssi + 1;
+            star = Some((spi, ssi + 1));
+        } else {
+            return false;
+        }
+    }
+
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+impl DevInfo {
+    /**
+     * Return every node whose devfs path matches the given pattern; e.g.,
+     * "/pci@0,0" to select just that node.  See Selector for the pattern
+     * syntax.
+     */
+    pub fn select(&self, pattern: &str) -> Result<Vec<Node<'_>>> {
+        self.select_with(&pattern.parse()?)
+    }
+
+    /**
+     * Return every node that matches the given selector.
+     */
+    pub fn select_with(&self, selector: &Selector) -> Result<Vec<Node<'_>>> {
+        let mut out = Vec::new();
+        for n in self.walk_node() {
+            let n = n?;
+            if selector.matches(&n)? {
+                out.push(n);
+            }
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn path_matches(pattern: &str, path: &str) -> bool {
+        let sel: Selector = pattern.parse().unwrap();
+        let components: Vec<&str> =
+            path.split('/').filter(|c| !c.is_empty()).collect();
+        sel.matches_path(&components)
+    }
+
+    #[test]
+    fn glob() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*", "pci@0,0"));
+        assert!(glob_match("pci*", "pci1022,1483@1,2"));
+        assert!(glob_match("disk@?", "disk@0"));
+        assert!(glob_match("*sas", "mpt_sas"));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(glob_match("blkdev@w*,0", "blkdev@w0014EE8401D04A00,0"));
+
+        assert!(!glob_match("disk@?", "disk@10"));
+        assert!(!glob_match("pci*", "pseudo"));
+        assert!(!glob_match("*sas", "mpt_sas2"));
+        assert!(!glob_match("", "x"));
+        assert!(!glob_match("a*b*c", "aXbYbZ"));
+    }
+
+    #[test]
+    fn paths() {
+        assert!(path_matches("/", "/"));
+        assert!(path_matches("/pci@0,0/pci*", "/pci@0,0/pci1022,1483@1,2"));
+        assert!(path_matches(
+            "/pci@0,0/pci*/disk@*",
+            "/pci@0,0/pci1,2@1/disk@0"
+        ));
+        assert!(path_matches("/**/disk@*", "/disk@0"));
+        assert!(path_matches("/**/disk@*", "/pci@0,0/pci1,2@1/disk@0"));
+        assert!(path_matches("/pci@0,0/**", "/pci@0,0"));
+
+        /*
+         * A "*" does not match across components, and the pattern must
+         * match the whole path.
+         */
+        assert!(!path_matches("/pci*", "/pci@0,0/pci1022,1483@1,2"));
+        assert!(!path_matches("/pci@0,0/*", "/pci@0,0"));
+        assert!(!path_matches("/**/disk@*", "/pci@0,0/disk@0/x@1"));
+    }
+
+    #[test]
+    fn malformed() {
+        assert!("pci@0,0/*".parse::<Selector>().is_err());
+        assert!("".parse::<Selector>().is_err());
+    }
+}
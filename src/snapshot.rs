@@ -0,0 +1,164 @@
+/*
+ * Copyright 2024 Oxide Computer Company
+ */
+
+use crate::sys::*;
+use crate::DevInfo;
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+bitflags::bitflags! {
+    /**
+     * Flags that control which information is included in a snapshot taken
+     * with di_init(3DEVINFO).  The DIIOC prefix common to all of the flags is
+     * added when the snapshot is taken; an empty set of flags produces a
+     * snapshot of just the requested node, without properties or minors.
+     */
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub struct SnapshotFlags: u32 {
+        /**
+         * Include the subtree below the requested node.
+         */
+        const SUBTREE = DINFOSUBTREE & !DIIOC;
+        /**
+         * Include properties.
+         */
+        const PROP = DINFOPROP & !DIIOC;
+        /**
+         * Include minor nodes.
+         */
+        const MINOR = DINFOMINOR & !DIIOC;
+        /**
+         * Include multipath (I/O path) information.
+         */
+        const PATH = DINFOPATH & !DIIOC;
+        /**
+         * Include device layering information.
+         */
+        const LYR = DINFOLYR & !DIIOC;
+        /**
+         * Include hotplug connection information.
+         */
+        const HP = DINFOHP & !DIIOC;
+        /**
+         * Use the snapshot cached by the system, if it is valid.
+         */
+        #[cfg(feature = "private")]
+        const CACHE = DINFOCACHE & !DIIOC;
+        /**
+         * Clean up stale /etc files as part of taking the snapshot.
+         */
+        #[cfg(feature = "private")]
+        const CLEANUP = DINFOCLEANUP & !DIIOC;
+        /**
+         * Force load and attach all drivers before taking the snapshot.
+         */
+        #[cfg(feature = "private")]
+        const FORCE = DINFOFORCE & !DIIOC;
+
+        /**
+         * The subtree, with properties and minor nodes; i.e., DINFOCPYALL.
+         */
+        const CPYALL = Self::SUBTREE.bits()
+            | Self::PROP.bits()
+            | Self::MINOR.bits();
+    }
+}
+
+impl SnapshotFlags {
+    pub(crate) fn to_raw(self) -> u32 {
+        DIIOC | self.bits()
+    }
+}
+
+impl Default for SnapshotFlags {
+    fn default() -> Self {
+        SnapshotFlags::CPYALL
+    }
+}
+
+/**
+ * Compose the options for a device tree snapshot, as returned by
+ * DevInfo::builder().  By default, the snapshot begins at the root of the tree
+ * and uses SnapshotFlags::CPYALL.
+ */
+#[derive(Clone, Debug)]
+pub struct DevInfoBuilder {
+    path: PathBuf,
+    driver: Option<String>,
+    flags: SnapshotFlags,
+}
+
+impl DevInfoBuilder {
+    pub(crate) fn new() -> Self {
+        DevInfoBuilder {
+            path: "/".into(),
+            driver: None,
+            flags: SnapshotFlags::default(),
+        }
+    }
+
+    /**
+     * The devfs path of the node at which the snapshot begins; e.g., "/" for
+     * the whole tree.
+     */
+    pub fn path<P: AsRef<Path>>(mut self, p: P) -> Self {
+        self.path = p.as_ref().to_path_buf();
+        self
+    }
+
+    /**
+     * Restrict the snapshot to the nodes of a single driver (e.g., "nvme"),
+     * using di_init_driver(3DEVINFO).  This is much cheaper than a snapshot
+     * of the whole tree when only one driver is of interest.  The path, if
+     * set, is ignored.
+     */
+    pub fn driver(mut self, driver: &str) -> Self {
+        self.driver = Some(driver.to_string());
+        self
+    }
+
+    /**
+     * Replace the set of flags used for the snapshot.
+     */
+    pub fn flags(mut self, flags: SnapshotFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /**
+     * Add to the set of flags used for the snapshot.
+     */
+    pub fn with(mut self, flags: SnapshotFlags) -> Self {
+        self.flags |= flags;
+        self
+    }
+
+    /**
+     * Use the snapshot cached by the system, if it is still valid, rather
+     * than having the kernel walk the device tree again.  This is much faster
+     * for tools that take snapshots frequently.  The cache only covers the
+     * whole tree, so the path must be "/" and no driver may be set.
+     */
+    #[cfg(feature = "private")]
+    pub fn cached(self) -> Self {
+        self.with(SnapshotFlags::CACHE)
+    }
+
+    pub fn build(self) -> Result<DevInfo> {
+        #[cfg(feature = "private")]
+        if self.flags.contains(SnapshotFlags::CACHE)
+            && (self.driver.is_some() || self.path != Path::new("/"))
+        {
+            anyhow::bail!(
+                "cached snapshots are only available for the whole tree"
+            );
+        }
+
+        if let Some(driver) = self.driver {
+            DevInfo::new_driver_common(&driver, self.flags)
+        } else {
+            DevInfo::new_common(self.path, self.flags)
+        }
+    }
+}
@@ -0,0 +1,440 @@
+/*
+ * Copyright 2024 Oxide Computer Company
+ */
+
+/*
+ * Everything from libdevinfo-sys is re-exported from here, along with
+ * declarations for any libdevinfo interfaces that crate does not yet provide,
+ * so that the rest of this crate can import from a single place.
+ */
+
+#![allow(non_camel_case_types)]
+
+use anyhow::Result;
+use libc::{dev_t, time_t};
+use std::os::raw::{c_char, c_int, c_uchar, c_uint};
+
+/*
+ * Declare a set of libdevinfo functions.  On illumos these are imported from
+ * the library as usual.  On other platforms, where there is no libdevinfo to
+ * link against, each function is instead a stub that panics if called.  The
+ * stubs are never reached in practice, as the constructors for every handle
+ * type call check_platform() first and report an error.  Functions from
+ * another system library can be declared the same way by giving the name of
+ * the library first, with "@link".
+ */
+macro_rules! devinfo_fns {
+    (@link $lib:literal;
+        $($(#[$attr:meta])* pub fn $name:ident($($arg:ident: $ty:ty),* $(,)?)
+        $(-> $ret:ty)?;)*) => {
+        #[cfg(target_os = "illumos")]
+        #[link(name = $lib)]
+        extern "C" {
+            $($(#[$attr])* pub fn $name($($arg: $ty),*) $(-> $ret)?;)*
+        }
+
+        $(
+            #[cfg(not(target_os = "illumos"))]
+            $(#[$attr])*
+            pub unsafe extern "C" fn $name($($arg: $ty),*) $(-> $ret)? {
+                let _ = ($($arg,)*);
+                unsupported(stringify!($name))
+            }
+        )*
+    };
+    ($($t:tt)*) => {
+        devinfo_fns! { @link "devinfo"; $($t)* }
+    };
+}
+
+#[cfg(target_os = "illumos")]
+pub use libdevinfo_sys::*;
+
+#[cfg(not(target_os = "illumos"))]
+mod stub;
+#[cfg(not(target_os = "illumos"))]
+pub use stub::*;
+
+#[cfg(not(target_os = "illumos"))]
+fn unsupported(name: &str) -> ! {
+    panic!("{}: libdevinfo is not available on this platform", name);
+}
+
+/**
+ * Every constructor that would otherwise obtain a handle from libdevinfo calls
+ * this first, so that on platforms other than illumos the consumer gets an
+ * error rather than a panic.
+ */
+#[cfg(target_os = "illumos")]
+pub fn check_platform() -> Result<()> {
+    Ok(())
+}
+
+#[cfg(not(target_os = "illumos"))]
+pub fn check_platform() -> Result<()> {
+    anyhow::bail!(
+        "libdevinfo is only available on illumos, not {}",
+        std::env::consts::OS
+    );
+}
+
+#[repr(C)]
+pub struct di_prom_handle_t {
+    _data: [u8; 0],
+}
+
+#[repr(C)]
+pub struct di_prom_prop_t {
+    _data: [u8; 0],
+}
+
+pub const DI_PROM_HANDLE_NIL: *mut di_prom_handle_t = std::ptr::null_mut();
+pub const DI_PROM_PROP_NIL: *mut di_prom_prop_t = std::ptr::null_mut();
+
+#[repr(C)]
+pub struct di_hp_t {
+    _data: [u8; 0],
+}
+
+pub const DI_HP_NIL: *mut di_hp_t = std::ptr::null_mut();
+
+#[repr(C)]
+pub struct di_path_t {
+    _data: [u8; 0],
+}
+
+#[repr(C)]
+pub struct di_path_prop_t {
+    _data: [u8; 0],
+}
+
+pub const DI_PATH_NIL: *mut di_path_t = std::ptr::null_mut();
+pub const DI_PATH_PROP_NIL: *mut di_path_prop_t = std::ptr::null_mut();
+
+/*
+ * Layering information.  Note that DI_LINK_NIL in libdevinfo-sys refers to a
+ * devlink handle, so a different name is used for the layered link here.
+ */
+#[repr(C)]
+pub struct di_lnode_t {
+    _data: [u8; 0],
+}
+
+#[repr(C)]
+pub struct di_link_t {
+    _data: [u8; 0],
+}
+
+pub const DI_LNODE_NIL: *mut di_lnode_t = std::ptr::null_mut();
+pub const DI_LYR_LINK_NIL: *mut di_link_t = std::ptr::null_mut();
+
+pub const DI_LINK_SRC: c_uint = 1;
+pub const DI_LINK_TGT: c_uint = 2;
+
+/*
+ * Snapshot flags for di_init(3DEVINFO), from <sys/devinfo_impl.h> and
+ * <libdevinfo.h>.
+ */
+pub const DIIOC: c_uint = 0xdf << 8;
+pub const DINFOPATH: c_uint = DIIOC | 0x08;
+pub const DINFOLYR: c_uint = DIIOC | 0x40;
+pub const DINFOHP: c_uint = DIIOC | 0x400000;
+#[cfg(feature = "private")]
+pub const DINFOCACHE: c_uint = DIIOC | 0x100000;
+#[cfg(feature = "private")]
+pub const DINFOCLEANUP: c_uint = DIIOC | 0x200000;
+
+/*
+ * Node state flags returned by di_state(3DEVINFO).
+ */
+pub const DI_DRIVER_DETACHED: c_uint = 0x8000;
+
+/*
+ * Special dev_t values from <sys/ddipropdefs.h>.  A property that is not
+ * associated with a particular minor node has a dev_t of DDI_DEV_T_NONE, and
+ * DDI_DEV_T_ANY is used to look up properties without regard to the minor
+ * node with which they are associated.
+ */
+pub const DDI_DEV_T_NONE: dev_t = dev_t::MAX;
+pub const DDI_DEV_T_ANY: dev_t = dev_t::MAX - 1;
+
+devinfo_fns! {
+    pub fn di_init_driver(
+        drv_name: *const c_char,
+        flag: c_uint,
+    ) -> *mut di_node_t;
+
+    pub fn di_bus_addr(node: *mut di_node_t) -> *const c_char;
+    pub fn di_state(node: *mut di_node_t) -> c_uint;
+    pub fn di_lookup_node(
+        root: *mut di_node_t,
+        path: *const c_char,
+    ) -> *mut di_node_t;
+
+    pub fn di_prop_hw_next(
+        node: *mut di_node_t,
+        prop: *mut di_prop_t,
+    ) -> *mut di_prop_t;
+    pub fn di_prop_drv_next(
+        node: *mut di_node_t,
+        prop: *mut di_prop_t,
+    ) -> *mut di_prop_t;
+    pub fn di_prop_sys_next(
+        node: *mut di_node_t,
+        prop: *mut di_prop_t,
+    ) -> *mut di_prop_t;
+    pub fn di_prop_global_next(
+        node: *mut di_node_t,
+        prop: *mut di_prop_t,
+    ) -> *mut di_prop_t;
+
+    pub fn di_prom_init() -> *mut di_prom_handle_t;
+    pub fn di_prom_fini(ph: *mut di_prom_handle_t);
+    pub fn di_prom_prop_next(
+        ph: *mut di_prom_handle_t,
+        node: *mut di_node_t,
+        prom_prop: *mut di_prom_prop_t,
+    ) -> *mut di_prom_prop_t;
+    pub fn di_prom_prop_name(prom_prop: *mut di_prom_prop_t) -> *const c_char;
+    pub fn di_prom_prop_data(
+        prom_prop: *mut di_prom_prop_t,
+        prom_prop_data: *mut *mut c_uchar,
+    ) -> c_int;
+    pub fn di_prom_prop_lookup_ints(
+        ph: *mut di_prom_handle_t,
+        node: *mut di_node_t,
+        prop_name: *const c_char,
+        prop_data: *mut *mut c_int,
+    ) -> c_int;
+    pub fn di_prom_prop_lookup_strings(
+        ph: *mut di_prom_handle_t,
+        node: *mut di_node_t,
+        prop_name: *const c_char,
+        prop_data: *mut *mut c_char,
+    ) -> c_int;
+    pub fn di_prom_prop_lookup_bytes(
+        ph: *mut di_prom_handle_t,
+        node: *mut di_node_t,
+        prop_name: *const c_char,
+        prop_data: *mut *mut c_uchar,
+    ) -> c_int;
+
+    pub fn di_prop_devt(prop: *mut di_prop_t) -> dev_t;
+
+    pub fn di_minor_devt(minor: *mut di_minor_t) -> dev_t;
+    pub fn di_minor_devinfo(minor: *mut di_minor_t) -> *mut di_node_t;
+
+    pub fn di_prop_lookup_ints(
+        dev: dev_t,
+        node: *mut di_node_t,
+        prop_name: *const c_char,
+        prop_data: *mut *mut c_int,
+    ) -> c_int;
+    pub fn di_prop_lookup_int64(
+        dev: dev_t,
+        node: *mut di_node_t,
+        prop_name: *const c_char,
+        prop_data: *mut *mut i64,
+    ) -> c_int;
+    pub fn di_prop_lookup_strings(
+        dev: dev_t,
+        node: *mut di_node_t,
+        prop_name: *const c_char,
+        prop_data: *mut *mut c_char,
+    ) -> c_int;
+    pub fn di_prop_lookup_bytes(
+        dev: dev_t,
+        node: *mut di_node_t,
+        prop_name: *const c_char,
+        prop_data: *mut *mut c_uchar,
+    ) -> c_int;
+
+    pub fn di_hp_next(node: *mut di_node_t, hp: *mut di_hp_t) -> *mut di_hp_t;
+    pub fn di_hp_name(hp: *mut di_hp_t) -> *const c_char;
+    pub fn di_hp_connection(hp: *mut di_hp_t) -> c_int;
+    pub fn di_hp_depends_on(hp: *mut di_hp_t) -> c_int;
+    pub fn di_hp_state(hp: *mut di_hp_t) -> c_int;
+    pub fn di_hp_type(hp: *mut di_hp_t) -> c_int;
+    pub fn di_hp_description(hp: *mut di_hp_t) -> *const c_char;
+    pub fn di_hp_last_change(hp: *mut di_hp_t) -> time_t;
+    pub fn di_hp_child(hp: *mut di_hp_t) -> *mut di_node_t;
+
+    pub fn di_path_client_next_path(
+        node: *mut di_node_t,
+        path: *mut di_path_t,
+    ) -> *mut di_path_t;
+    pub fn di_path_phci_next_path(
+        node: *mut di_node_t,
+        path: *mut di_path_t,
+    ) -> *mut di_path_t;
+    pub fn di_path_client_node(path: *mut di_path_t) -> *mut di_node_t;
+    pub fn di_path_phci_node(path: *mut di_path_t) -> *mut di_node_t;
+    pub fn di_path_node_name(path: *mut di_path_t) -> *const c_char;
+    pub fn di_path_bus_addr(path: *mut di_path_t) -> *const c_char;
+    pub fn di_path_instance(path: *mut di_path_t) -> c_int;
+    pub fn di_path_state(path: *mut di_path_t) -> c_int;
+
+    pub fn di_path_prop_next(
+        path: *mut di_path_t,
+        prop: *mut di_path_prop_t,
+    ) -> *mut di_path_prop_t;
+    pub fn di_path_prop_name(prop: *mut di_path_prop_t) -> *const c_char;
+    pub fn di_path_prop_type(prop: *mut di_path_prop_t) -> c_int;
+    pub fn di_path_prop_bytes(
+        prop: *mut di_path_prop_t,
+        prop_data: *mut *mut c_uchar,
+    ) -> c_int;
+    pub fn di_path_prop_ints(
+        prop: *mut di_path_prop_t,
+        prop_data: *mut *mut c_int,
+    ) -> c_int;
+    pub fn di_path_prop_int64s(
+        prop: *mut di_path_prop_t,
+        prop_data: *mut *mut i64,
+    ) -> c_int;
+    pub fn di_path_prop_strings(
+        prop: *mut di_path_prop_t,
+        prop_data: *mut *mut c_char,
+    ) -> c_int;
+    pub fn di_path_prop_lookup_bytes(
+        path: *mut di_path_t,
+        prop_name: *const c_char,
+        prop_data: *mut *mut c_uchar,
+    ) -> c_int;
+    pub fn di_path_prop_lookup_ints(
+        path: *mut di_path_t,
+        prop_name: *const c_char,
+        prop_data: *mut *mut c_int,
+    ) -> c_int;
+    pub fn di_path_prop_lookup_int64s(
+        path: *mut di_path_t,
+        prop_name: *const c_char,
+        prop_data: *mut *mut i64,
+    ) -> c_int;
+    pub fn di_path_prop_lookup_strings(
+        path: *mut di_path_t,
+        prop_name: *const c_char,
+        prop_data: *mut *mut c_char,
+    ) -> c_int;
+
+    pub fn di_lnode_next(
+        node: *mut di_node_t,
+        lnode: *mut di_lnode_t,
+    ) -> *mut di_lnode_t;
+    pub fn di_lnode_name(lnode: *mut di_lnode_t) -> *const c_char;
+    pub fn di_lnode_devinfo(lnode: *mut di_lnode_t) -> *mut di_node_t;
+    pub fn di_lnode_devt(lnode: *mut di_lnode_t, devt: *mut dev_t) -> c_int;
+    pub fn di_link_next_by_node(
+        node: *mut di_node_t,
+        link: *mut di_link_t,
+        endpoint: c_uint,
+    ) -> *mut di_link_t;
+    pub fn di_link_to_lnode(
+        link: *mut di_link_t,
+        endpoint: c_uint,
+    ) -> *mut di_lnode_t;
+    pub fn di_link_next_by_lnode(
+        lnode: *mut di_lnode_t,
+        link: *mut di_link_t,
+        endpoint: c_uint,
+    ) -> *mut di_link_t;
+    pub fn di_link_spectype(link: *mut di_link_t) -> c_int;
+}
+
+/*
+ * Private interfaces:
+ */
+devinfo_fns! {
+    #[cfg(feature = "private")]
+    pub fn di_prop_rawdata(
+        prop: *mut di_prop_t,
+        prop_data: *mut *mut c_uchar,
+    ) -> c_int;
+
+    #[cfg(feature = "private")]
+    pub fn di_devlink_open(
+        root_dir: *const c_char,
+        flags: c_uint,
+    ) -> *mut di_devlink_handle_t;
+    #[cfg(feature = "private")]
+    pub fn di_devlink_close(
+        hdlp: *mut *mut di_devlink_handle_t,
+        flag: c_int,
+    ) -> c_int;
+}
+
+/*
+ * Interfaces from libsysevent and libnvpair, used to subscribe to device
+ * events:
+ */
+#[cfg(feature = "events")]
+#[repr(C)]
+pub struct sysevent_t {
+    _data: [u8; 0],
+}
+
+#[cfg(feature = "events")]
+#[repr(C)]
+pub struct sysevent_handle_t {
+    _data: [u8; 0],
+}
+
+#[cfg(feature = "events")]
+#[repr(C)]
+pub struct nvlist_t {
+    _data: [u8; 0],
+}
+
+#[cfg(feature = "events")]
+pub type sysevent_handler_t = unsafe extern "C" fn(ev: *mut sysevent_t);
+
+devinfo_fns! {
+    @link "sysevent";
+
+    #[cfg(feature = "events")]
+    pub fn sysevent_bind_handle(
+        event_handler: sysevent_handler_t,
+    ) -> *mut sysevent_handle_t;
+    #[cfg(feature = "events")]
+    pub fn sysevent_unbind_handle(shp: *mut sysevent_handle_t);
+    #[cfg(feature = "events")]
+    pub fn sysevent_subscribe_event(
+        shp: *mut sysevent_handle_t,
+        event_class: *const c_char,
+        event_subclass_list: *mut *const c_char,
+        num_subclasses: c_int,
+    ) -> c_int;
+    #[cfg(feature = "events")]
+    pub fn sysevent_unsubscribe_event(
+        shp: *mut sysevent_handle_t,
+        event_class: *const c_char,
+    );
+    #[cfg(feature = "events")]
+    pub fn sysevent_get_class_name(ev: *mut sysevent_t) -> *const c_char;
+    #[cfg(feature = "events")]
+    pub fn sysevent_get_subclass_name(ev: *mut sysevent_t) -> *const c_char;
+    #[cfg(feature = "events")]
+    pub fn sysevent_get_attr_list(
+        ev: *mut sysevent_t,
+        nvlist: *mut *mut nvlist_t,
+    ) -> c_int;
+}
+
+devinfo_fns! {
+    @link "nvpair";
+
+    #[cfg(feature = "events")]
+    pub fn nvlist_free(nvl: *mut nvlist_t);
+    #[cfg(feature = "events")]
+    pub fn nvlist_lookup_string(
+        nvl: *mut nvlist_t,
+        name: *const c_char,
+        val: *mut *mut c_char,
+    ) -> c_int;
+    #[cfg(feature = "events")]
+    pub fn nvlist_lookup_int32(
+        nvl: *mut nvlist_t,
+        name: *const c_char,
+        val: *mut i32,
+    ) -> c_int;
+}
@@ -0,0 +1,163 @@
+/*
+ * Copyright 2024 Oxide Computer Company
+ */
+
+/*
+ * Stand-ins for the parts of libdevinfo-sys used by this crate, for platforms
+ * other than illumos.  These allow the crate (and crates that depend on it) to
+ * build anywhere, though no handle can be obtained at runtime.  Not every
+ * item here is used on every platform or with every feature, just as with the
+ * real crate.
+ */
+
+#![allow(dead_code)]
+
+use super::unsupported;
+use std::os::raw::{c_char, c_int, c_uchar, c_uint, c_void};
+
+#[repr(C)]
+pub struct di_node_t {
+    _data: [u8; 0],
+}
+
+#[repr(C)]
+pub struct di_minor_t {
+    _data: [u8; 0],
+}
+
+#[repr(C)]
+pub struct di_prop_t {
+    _data: [u8; 0],
+}
+
+#[repr(C)]
+pub struct di_devlink_handle_t {
+    _data: [u8; 0],
+}
+
+#[repr(C)]
+pub struct di_devlink_t {
+    _data: [u8; 0],
+}
+
+#[cfg(feature = "private")]
+#[repr(C)]
+pub struct di_dim_t {
+    _data: [u8; 0],
+}
+
+pub const DI_NODE_NIL: *mut di_node_t = std::ptr::null_mut();
+pub const DI_MINOR_NIL: *mut di_minor_t = std::ptr::null_mut();
+pub const DI_PROP_NIL: *mut di_prop_t = std::ptr::null_mut();
+pub const DI_LINK_NIL: *mut di_devlink_handle_t = std::ptr::null_mut();
+
+pub const DINFOSUBTREE: c_uint = super::DIIOC | 0x01;
+pub const DINFOMINOR: c_uint = super::DIIOC | 0x02;
+pub const DINFOPROP: c_uint = super::DIIOC | 0x04;
+pub const DINFOCPYALL: c_uint = DINFOSUBTREE | DINFOPROP | DINFOMINOR;
+#[cfg(feature = "private")]
+pub const DINFOFORCE: c_uint = super::DIIOC | 0x20;
+
+pub const DI_PROP_TYPE_BOOLEAN: c_int = 0;
+pub const DI_PROP_TYPE_INT: c_int = 1;
+pub const DI_PROP_TYPE_STRING: c_int = 2;
+pub const DI_PROP_TYPE_BYTE: c_int = 3;
+pub const DI_PROP_TYPE_UNKNOWN: c_int = 4;
+pub const DI_PROP_TYPE_UNDEF_IT: c_int = 5;
+pub const DI_PROP_TYPE_INT64: c_int = 6;
+
+pub const DI_WALK_CONTINUE: c_int = 0;
+pub const DI_WALK_PRUNESIB: c_int = -1;
+pub const DI_WALK_PRUNECHILD: c_int = -2;
+pub const DI_WALK_TERMINATE: c_int = -3;
+
+pub const DI_MAKE_LINK: c_uint = 0x01;
+pub const DI_PRIMARY_LINK: c_uint = 0x01;
+pub const DI_SECONDARY_LINK: c_uint = 0x02;
+
+devinfo_fns! {
+    pub fn di_init(phys_path: *const c_char, flag: c_uint) -> *mut di_node_t;
+    pub fn di_fini(root: *mut di_node_t);
+
+    pub fn di_parent_node(node: *mut di_node_t) -> *mut di_node_t;
+    pub fn di_sibling_node(node: *mut di_node_t) -> *mut di_node_t;
+    pub fn di_child_node(node: *mut di_node_t) -> *mut di_node_t;
+    pub fn di_drv_first_node(
+        drv_name: *const c_char,
+        root: *mut di_node_t,
+    ) -> *mut di_node_t;
+    pub fn di_drv_next_node(node: *mut di_node_t) -> *mut di_node_t;
+
+    pub fn di_node_name(node: *mut di_node_t) -> *const c_char;
+    pub fn di_driver_name(node: *mut di_node_t) -> *const c_char;
+    pub fn di_instance(node: *mut di_node_t) -> c_int;
+    pub fn di_devfs_path(node: *mut di_node_t) -> *mut c_char;
+    pub fn di_devfs_minor_path(minor: *mut di_minor_t) -> *mut c_char;
+    pub fn di_devfs_path_free(path_buf: *mut c_char);
+
+    pub fn di_minor_next(
+        node: *mut di_node_t,
+        minor: *mut di_minor_t,
+    ) -> *mut di_minor_t;
+    pub fn di_minor_name(minor: *mut di_minor_t) -> *const c_char;
+    pub fn di_minor_nodetype(minor: *mut di_minor_t) -> *const c_char;
+    pub fn di_minor_spectype(minor: *mut di_minor_t) -> c_int;
+
+    pub fn di_prop_next(
+        node: *mut di_node_t,
+        prop: *mut di_prop_t,
+    ) -> *mut di_prop_t;
+    pub fn di_prop_name(prop: *mut di_prop_t) -> *const c_char;
+    pub fn di_prop_type(prop: *mut di_prop_t) -> c_int;
+    pub fn di_prop_ints(prop: *mut di_prop_t, prop_data: *mut *mut c_int)
+        -> c_int;
+    pub fn di_prop_int64(prop: *mut di_prop_t, prop_data: *mut *mut i64)
+        -> c_int;
+    pub fn di_prop_strings(
+        prop: *mut di_prop_t,
+        prop_data: *mut *mut c_char,
+    ) -> c_int;
+    pub fn di_prop_bytes(
+        prop: *mut di_prop_t,
+        prop_data: *mut *mut c_uchar,
+    ) -> c_int;
+
+    pub fn di_devlink_init(
+        name: *const c_char,
+        flags: c_uint,
+    ) -> *mut di_devlink_handle_t;
+    pub fn di_devlink_fini(hdlp: *mut *mut di_devlink_handle_t) -> c_int;
+    pub fn di_devlink_walk(
+        hdl: *mut di_devlink_handle_t,
+        re: *const c_char,
+        minor_path: *const c_char,
+        flags: c_uint,
+        arg: *mut c_void,
+        devlink_callback: extern "C" fn(
+            *const di_devlink_t,
+            *mut c_void,
+        ) -> c_int,
+    ) -> c_int;
+    pub fn di_devlink_path(devlink: *const di_devlink_t) -> *const c_char;
+    pub fn di_devlink_content(devlink: *const di_devlink_t) -> *const c_char;
+    pub fn di_devlink_type(devlink: *const di_devlink_t) -> c_int;
+
+    #[cfg(feature = "private")]
+    pub fn di_dim_init() -> *mut di_dim_t;
+    #[cfg(feature = "private")]
+    pub fn di_dim_fini(hdl: *mut di_dim_t);
+    #[cfg(feature = "private")]
+    pub fn di_dim_path_devices(
+        hdl: *mut di_dim_t,
+        drv_name: *const c_char,
+        instance: c_int,
+        minor_name: *const c_char,
+    ) -> *mut c_char;
+    #[cfg(feature = "private")]
+    pub fn di_dim_path_dev(
+        hdl: *mut di_dim_t,
+        drv_name: *const c_char,
+        instance: c_int,
+        minor_name: *const c_char,
+    ) -> *mut c_char;
+}
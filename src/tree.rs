@@ -0,0 +1,320 @@
+/*
+ * Copyright 2024 Oxide Computer Company
+ */
+
+/*
+ * Traits that abstract over the live device tree (DevInfo) and the owned copy
+ * (OwnedDevTree), so that consumers can write code that works with either.
+ * Code written against these traits can be tested with a fabricated
+ * OwnedDevTree on systems without the hardware (or the operating system) of
+ * interest.
+ */
+
+use crate::sys::*;
+use crate::{
+    DevInfo, Minor, Node, OwnedDevTree, OwnedMinor, OwnedNode, PropValue,
+    SpecType,
+};
+use anyhow::Result;
+use libc::dev_t;
+
+pub trait DeviceTree {
+    type NodeRef<'a>: TreeNode
+    where
+        Self: 'a;
+
+    fn root(&self) -> Self::NodeRef<'_>;
+
+    /**
+     * Return every node in the tree, in depth-first order.
+     */
+    fn nodes(&self) -> Vec<Self::NodeRef<'_>> {
+        let mut out = Vec::new();
+        let mut stack = vec![self.root()];
+        while let Some(n) = stack.pop() {
+            stack.extend(n.children().into_iter().rev());
+            out.push(n);
+        }
+        out
+    }
+
+    /**
+     * Find the node with the given devfs path; e.g., "/pci@0,0".
+     */
+    fn find(&self, devfs_path: &str) -> Option<Self::NodeRef<'_>> {
+        self.nodes()
+            .into_iter()
+            .find(|n| n.devfs_path().is_ok_and(|p| p == devfs_path))
+    }
+}
+
+pub trait TreeNode: Clone + Sized {
+    type MinorRef: TreeMinor;
+
+    fn node_name(&self) -> String;
+    fn driver_name(&self) -> Option<String>;
+    fn instance(&self) -> Option<i32>;
+    fn unit_address(&self) -> Option<String>;
+    fn devfs_path(&self) -> Result<String>;
+    fn parent(&self) -> Option<Self>;
+    fn children(&self) -> Vec<Self>;
+    fn minors(&self) -> Vec<Self::MinorRef>;
+
+    /**
+     * Look up a property by name, returning its decoded value.
+     */
+    fn prop(&self, name: &str) -> Option<PropValue>;
+}
+
+pub trait TreeMinor {
+    fn name(&self) -> String;
+    fn node_type(&self) -> String;
+    fn spec_type(&self) -> Option<SpecType>;
+    fn devt(&self) -> dev_t;
+    fn devfs_path(&self) -> Result<String>;
+}
+
+impl DeviceTree for DevInfo {
+    type NodeRef<'a> = Node<'a>;
+
+    fn root(&self) -> Node<'_> {
+        Node { parent: self, node: self.root }
+    }
+
+    fn find(&self, devfs_path: &str) -> Option<Node<'_>> {
+        self.node_by_path(devfs_path).ok().flatten()
+    }
+}
+
+impl<'a> TreeNode for Node<'a> {
+    type MinorRef = Minor<'a>;
+
+    fn node_name(&self) -> String {
+        Node::node_name(self)
+    }
+
+    fn driver_name(&self) -> Option<String> {
+        Node::driver_name(self)
+    }
+
+    fn instance(&self) -> Option<i32> {
+        Node::instance(self)
+    }
+
+    fn unit_address(&self) -> Option<String> {
+        Node::unit_address(self)
+    }
+
+    fn devfs_path(&self) -> Result<String> {
+        Node::devfs_path(self)
+    }
+
+    fn parent(&self) -> Option<Self> {
+        Node::parent(self).ok().flatten()
+    }
+
+    fn children(&self) -> Vec<Self> {
+        let mut out = Vec::new();
+        let mut child = unsafe { di_child_node(self.node) };
+        while child != DI_NODE_NIL {
+            out.push(Node { parent: self.parent, node: child });
+            child = unsafe { di_sibling_node(child) };
+        }
+        out
+    }
+
+    fn minors(&self) -> Vec<Minor<'a>> {
+        Node::minors(self).filter_map(|m| m.ok()).collect()
+    }
+
+    fn prop(&self, name: &str) -> Option<PropValue> {
+        Node::props(self)
+            .filter_map(|p| p.ok())
+            .find(|p| {
+                p.devt().dev().is_none() && p.name_bytes() == name.as_bytes()
+            })?
+            .value()
+            .ok()
+    }
+}
+
+impl TreeMinor for Minor<'_> {
+    fn name(&self) -> String {
+        Minor::name(self)
+    }
+
+    fn node_type(&self) -> String {
+        Minor::node_type(self)
+    }
+
+    fn spec_type(&self) -> Option<SpecType> {
+        Minor::try_spec_type(self)
+    }
+
+    fn devt(&self) -> dev_t {
+        Minor::devt(self)
+    }
+
+    fn devfs_path(&self) -> Result<String> {
+        Minor::devfs_path(self)
+    }
+}
+
+/**
+ * A reference to a node within an OwnedDevTree.  The ancestors of the node
+ * are recorded as it is reached from the root, so that the parent can be
+ * found without searching the tree.
+ */
+#[derive(Clone, Debug)]
+pub struct OwnedNodeRef<'a> {
+    node: &'a OwnedNode,
+    ancestors: Vec<&'a OwnedNode>,
+}
+
+impl<'a> OwnedNodeRef<'a> {
+    pub fn node(&self) -> &'a OwnedNode {
+        self.node
+    }
+
+    fn child(&self, node: &'a OwnedNode) -> Self {
+        let mut ancestors = self.ancestors.clone();
+        ancestors.push(self.node);
+        OwnedNodeRef { node, ancestors }
+    }
+}
+
+impl DeviceTree for OwnedDevTree {
+    type NodeRef<'a> = OwnedNodeRef<'a>;
+
+    fn root(&self) -> OwnedNodeRef<'_> {
+        OwnedNodeRef { node: &self.root, ancestors: Vec::new() }
+    }
+
+    /*
+     * Descend from the root through the node on the path at each level,
+     * rather than searching the whole tree.
+     */
+    fn find(&self, devfs_path: &str) -> Option<OwnedNodeRef<'_>> {
+        let mut n = self.root();
+        while n.node.devfs_path != devfs_path {
+            let next = n.node.children.iter().find(|c| {
+                devfs_path.strip_prefix(c.devfs_path.as_str()).is_some_and(
+                    |rest| rest.is_empty() || rest.starts_with('/'),
+                )
+            })?;
+            n = n.child(next);
+        }
+        Some(n)
+    }
+}
+
+impl<'a> TreeNode for OwnedNodeRef<'a> {
+    type MinorRef = &'a OwnedMinor;
+
+    fn node_name(&self) -> String {
+        self.node.node_name.clone()
+    }
+
+    fn driver_name(&self) -> Option<String> {
+        self.node.driver_name.clone()
+    }
+
+    fn instance(&self) -> Option<i32> {
+        self.node.instance
+    }
+
+    fn unit_address(&self) -> Option<String> {
+        self.node.unit_address.clone()
+    }
+
+    fn devfs_path(&self) -> Result<String> {
+        Ok(self.node.devfs_path.clone())
+    }
+
+    fn parent(&self) -> Option<Self> {
+        let (&node, ancestors) = self.ancestors.split_last()?;
+        Some(OwnedNodeRef { node, ancestors: ancestors.to_vec() })
+    }
+
+    fn children(&self) -> Vec<Self> {
+        self.node.children.iter().map(|c| self.child(c)).collect()
+    }
+
+    fn minors(&self) -> Vec<&'a OwnedMinor> {
+        self.node.minors.iter().collect()
+    }
+
+    fn prop(&self, name: &str) -> Option<PropValue> {
+        self.node.prop(name).cloned()
+    }
+}
+
+impl TreeMinor for &OwnedMinor {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn node_type(&self) -> String {
+        self.node_type.clone()
+    }
+
+    fn spec_type(&self) -> Option<SpecType> {
+        self.spec_type
+    }
+
+    fn devt(&self) -> dev_t {
+        self.devt
+    }
+
+    fn devfs_path(&self) -> Result<String> {
+        Ok(self.devfs_path.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn node(path: &str, children: Vec<OwnedNode>) -> OwnedNode {
+        OwnedNode {
+            node_name: path.rsplit('/').next().unwrap().to_string(),
+            driver_name: None,
+            instance: None,
+            unit_address: None,
+            devfs_path: path.to_string(),
+            props: Vec::new(),
+            minors: Vec::new(),
+            children,
+        }
+    }
+
+    #[test]
+    fn find_and_parent() {
+        let tree = OwnedDevTree {
+            root: node(
+                "/",
+                vec![
+                    node("/pci@0", vec![node("/pci@0/disk@1", Vec::new())]),
+                    node("/pci@0,1", vec![node("/pci@0,1/disk@1", Vec::new())]),
+                ],
+            ),
+        };
+
+        let n = DeviceTree::find(&tree, "/pci@0,1/disk@1").unwrap();
+        let parents: Vec<_> = std::iter::successors(Some(n), |n| n.parent())
+            .map(|n| n.devfs_path().unwrap())
+            .collect();
+        assert_eq!(parents, ["/pci@0,1/disk@1", "/pci@0,1", "/"]);
+
+        assert!(DeviceTree::find(&tree, "/pci@0/disk").is_none());
+        assert!(DeviceTree::find(&tree, "/pci@1").is_none());
+        assert!(DeviceTree::find(&tree, "/").unwrap().parent().is_none());
+
+        let all: Vec<_> =
+            tree.nodes().iter().map(|n| n.devfs_path().unwrap()).collect();
+        assert_eq!(
+            all,
+            ["/", "/pci@0", "/pci@0/disk@1", "/pci@0,1", "/pci@0,1/disk@1"]
+        );
+    }
+}
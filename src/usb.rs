@@ -0,0 +1,827 @@
+/*
+ * Copyright 2024 Oxide Computer Company
+ */
+
+/*
+ * USB devices, as enumerated by usba(4D).  Each device is a child of the hub
+ * to which it is connected, with a unit address that is the number of the
+ * hub port, and each hub is in turn a child of another hub or of the root hub
+ * provided by a host controller (e.g., xhci(4D)).
+ */
+
+use crate::sys::*;
+use crate::{
+    lookup_strings, DevInfo, DevLink, DevLinks, Node, TreeNode,
+    DEVICE_POLL_INTERVAL,
+};
+use anyhow::{bail, Result};
+use std::time::{Duration, Instant};
+
+/*
+ * Descriptor types, from the USB specification.
+ */
+const USB_DESCR_TYPE_DEV: u8 = 1;
+const USB_DESCR_TYPE_CFG: u8 = 2;
+const USB_DESCR_TYPE_IF: u8 = 4;
+
+const USB_DEV_DESCR_SIZE: usize = 18;
+const USB_CFG_DESCR_SIZE: usize = 9;
+const USB_IF_DESCR_SIZE: usize = 9;
+
+/**
+ * The speed at which a USB device is operating.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum UsbSpeed {
+    Low,
+    Full,
+    High,
+    Super,
+}
+
+impl std::fmt::Display for UsbSpeed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            UsbSpeed::Low => "low-speed (1.5 Mbps)",
+            UsbSpeed::Full => "full-speed (12 Mbps)",
+            UsbSpeed::High => "high-speed (480 Mbps)",
+            UsbSpeed::Super => "super-speed (5 Gbps)",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/**
+ * A version number in the binary-coded decimal form used in USB descriptors;
+ * e.g., 0x0210 for version 2.10.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BcdVersion(pub u16);
+
+impl BcdVersion {
+    pub fn major(&self) -> u8 {
+        (self.0 >> 8) as u8
+    }
+
+    pub fn minor(&self) -> u8 {
+        (self.0 & 0xff) as u8
+    }
+}
+
+impl std::fmt::Display for BcdVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:x}.{:02x}", self.major(), self.minor())
+    }
+}
+
+/**
+ * The standard device descriptor, as read from the device during
+ * enumeration.  String descriptor indices are omitted; usba publishes the
+ * strings themselves as properties.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UsbDeviceDescriptor {
+    pub usb_version: BcdVersion,
+    pub class: u8,
+    pub subclass: u8,
+    pub protocol: u8,
+    pub max_packet_size0: u8,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub device_version: BcdVersion,
+    pub num_configurations: u8,
+}
+
+impl UsbDeviceDescriptor {
+    fn parse(b: &[u8]) -> Option<Self> {
+        if b.len() < USB_DEV_DESCR_SIZE || b[1] != USB_DESCR_TYPE_DEV {
+            return None;
+        }
+        let u16_at = |i: usize| u16::from_le_bytes([b[i], b[i + 1]]);
+
+        Some(UsbDeviceDescriptor {
+            usb_version: BcdVersion(u16_at(2)),
+            class: b[4],
+            subclass: b[5],
+            protocol: b[6],
+            max_packet_size0: b[7],
+            vendor_id: u16_at(8),
+            product_id: u16_at(10),
+            device_version: BcdVersion(u16_at(12)),
+            num_configurations: b[17],
+        })
+    }
+}
+
+/**
+ * A configuration descriptor, along with the interface descriptors that
+ * follow it.  Endpoint and class-specific descriptors are not decoded.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UsbConfigDescriptor {
+    pub value: u8,
+    pub attributes: u8,
+    /**
+     * The maximum power drawn from the bus in this configuration, in mA.
+     */
+    pub max_power_ma: u32,
+    pub interfaces: Vec<UsbInterfaceDescriptor>,
+}
+
+impl UsbConfigDescriptor {
+    /**
+     * Returns true if the device is self-powered in this configuration.
+     */
+    pub fn self_powered(&self) -> bool {
+        self.attributes & 0x40 != 0
+    }
+
+    /**
+     * Returns true if the device supports remote wakeup in this
+     * configuration.
+     */
+    pub fn remote_wakeup(&self) -> bool {
+        self.attributes & 0x20 != 0
+    }
+}
+
+/**
+ * An interface descriptor.  Each alternate setting of an interface has its
+ * own descriptor.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UsbInterfaceDescriptor {
+    pub number: u8,
+    pub alternate: u8,
+    pub num_endpoints: u8,
+    pub class: u8,
+    pub subclass: u8,
+    pub protocol: u8,
+}
+
+/*
+ * Walk a buffer of descriptors, each of which begins with its length and
+ * type, as returned for the GET_DESCRIPTOR(CONFIGURATION) request.  Several
+ * configurations may be concatenated.
+ */
+fn parse_cfg_descriptors(
+    mut b: &[u8],
+    speed: Option<UsbSpeed>,
+) -> Vec<UsbConfigDescriptor> {
+    /*
+     * bMaxPower is in units of 8mA for super-speed devices, and 2mA
+     * otherwise.
+     */
+    let power_unit = if speed == Some(UsbSpeed::Super) { 8 } else { 2 };
+    let mut out: Vec<UsbConfigDescriptor> = Vec::new();
+
+    while b.len() >= 2 {
+        let len = usize::from(b[0]);
+        if len < 2 || len > b.len() {
+            break;
+        }
+        let (d, rest) = b.split_at(len);
+        b = rest;
+
+        match d[1] {
+            USB_DESCR_TYPE_CFG if len >= USB_CFG_DESCR_SIZE => {
+                out.push(UsbConfigDescriptor {
+                    value: d[5],
+                    attributes: d[7],
+                    max_power_ma: u32::from(d[8]) * power_unit,
+                    interfaces: Vec::new(),
+                });
+            }
+            USB_DESCR_TYPE_IF if len >= USB_IF_DESCR_SIZE => {
+                let Some(cfg) = out.last_mut() else {
+                    continue;
+                };
+                cfg.interfaces.push(UsbInterfaceDescriptor {
+                    number: d[2],
+                    alternate: d[3],
+                    num_endpoints: d[4],
+                    class: d[5],
+                    subclass: d[6],
+                    protocol: d[7],
+                });
+            }
+            _ => {}
+        }
+    }
+
+    out
+}
+
+/*
+ * Host controller drivers, whose nodes also act as the root hub.  usba marks
+ * these nodes with the "root-hub" property, but the driver name is checked
+ * as well in case it is missing.
+ */
+const USB_HCD_DRIVERS: &[&str] = &["xhci", "ehci", "ohci", "uhci"];
+
+fn is_root_hub(n: &Node) -> bool {
+    n.prop_bool("root-hub")
+        || n.driver_name()
+            .is_some_and(|d| USB_HCD_DRIVERS.contains(&d.as_str()))
+}
+
+/*
+ * Interface nodes, created by usb_mid(4D) for each interface of a composite
+ * device, carry the same identifying properties as the device itself, but
+ * also have an "interface" property.
+ */
+fn is_usb_device(n: &Node) -> bool {
+    n.prop_i32("usb-vendor-id").is_some() && n.prop_i32("interface").is_none()
+}
+
+/*
+ * The unit address of a USB device node is the hub port number, in
+ * hexadecimal.  A node that has not been assigned an address (e.g., because
+ * it is still being enumerated) has no port number.
+ */
+fn port_number(n: &Node) -> Option<u32> {
+    u32::from_str_radix(&n.unit_address()?, 16).ok()
+}
+
+impl DevInfo {
+    /**
+     * Collect every USB device in the snapshot, including hubs other than
+     * root hubs.
+     */
+    pub fn usb_devices(&self) -> Result<Vec<UsbDevice<'_>>> {
+        let mut out = Vec::new();
+        for n in self.walk_node() {
+            if let Some(dev) = UsbDevice::from_node(n?)? {
+                out.push(dev);
+            }
+        }
+        Ok(out)
+    }
+
+    /**
+     * Find a USB device by its vendor and product ID and, optionally, its
+     * serial number.  If several devices match, the first one found is
+     * returned.
+     */
+    pub fn usb_device_by_id(
+        &self,
+        vendor: u16,
+        product: u16,
+        serial: Option<&str>,
+    ) -> Result<Option<UsbDevice<'_>>> {
+        Ok(self.usb_devices()?.into_iter().find(|d| {
+            d.vendor_id() == Some(vendor)
+                && d.product_id() == Some(product)
+                && (serial.is_none() || d.serial().as_deref() == serial)
+        }))
+    }
+}
+
+/**
+ * Wait for a USB device with the given vendor and product ID and, if
+ * provided, serial number to be enumerated and have a driver attached; e.g.,
+ * after power cycling a board with a debug probe.  A new snapshot is taken
+ * periodically until the device is found or the timeout expires.
+ */
+pub fn wait_for(
+    vendor: u16,
+    product: u16,
+    serial: Option<&str>,
+    timeout: Duration,
+) -> Result<FoundUsbDevice> {
+    let start = Instant::now();
+
+    loop {
+        let di = DevInfo::new()?;
+        let found = di
+            .usb_device_by_id(vendor, product, serial)?
+            .filter(|d| d.node().is_attached())
+            .map(|d| d.node().devfs_path())
+            .transpose()?;
+        if let Some(devfs_path) = found {
+            return Ok(FoundUsbDevice { di, devfs_path });
+        }
+
+        if start.elapsed() >= timeout {
+            bail!(
+                "USB device {:04x},{:04x}{} did not appear within {:?}",
+                vendor,
+                product,
+                serial.map(|s| format!(" ({})", s)).unwrap_or_default(),
+                timeout,
+            );
+        }
+
+        std::thread::sleep(
+            DEVICE_POLL_INTERVAL.min(timeout.saturating_sub(start.elapsed())),
+        );
+    }
+}
+
+/**
+ * A USB device found by wait_for(), along with the snapshot in which it was
+ * found.
+ */
+pub struct FoundUsbDevice {
+    di: DevInfo,
+    devfs_path: String,
+}
+
+impl FoundUsbDevice {
+    pub fn devinfo(&self) -> &DevInfo {
+        &self.di
+    }
+
+    /**
+     * The devfs path of the device node; e.g.,
+     * "/pci@0,0/pci1022,1639@8,1/pci1022,1639@0,3/device@2".
+     */
+    pub fn devfs_path(&self) -> &str {
+        &self.devfs_path
+    }
+
+    pub fn device(&self) -> Result<UsbDevice<'_>> {
+        let Some(n) = self.di.node_by_path(&self.devfs_path)? else {
+            bail!("{} is missing from the snapshot", self.devfs_path);
+        };
+        let Some(d) = UsbDevice::from_node(n)? else {
+            bail!("{} is no longer a USB device", self.devfs_path);
+        };
+        Ok(d)
+    }
+
+    /**
+     * Return the links in /dev for the minor nodes of the device and, for a
+     * composite device, of each of its interfaces; e.g., "/dev/term/0" for a
+     * serial console.
+     */
+    pub fn dev_links(&self) -> Result<Vec<DevLink>> {
+        let dev = self.device()?;
+        let dl = DevLinks::new(false)?;
+
+        let mut out = Vec::new();
+        let nodes = std::iter::once(dev.node().clone())
+            .chain(dev.interfaces().into_iter().map(|i| i.node().clone()));
+        for n in nodes {
+            out.extend(dl.links_for_node(&n)?.into_values().flatten());
+        }
+        Ok(out)
+    }
+}
+
+impl std::fmt::Debug for FoundUsbDevice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FoundUsbDevice")
+            .field("devfs_path", &self.devfs_path)
+            .finish()
+    }
+}
+
+/**
+ * A USB device, along with its position in the USB topology.
+ */
+#[derive(Clone)]
+pub struct UsbDevice<'a> {
+    node: Node<'a>,
+    controller: Node<'a>,
+    ports: Vec<u32>,
+}
+
+impl<'a> UsbDevice<'a> {
+    /**
+     * Determine the position of a device node in the USB topology by
+     * following its parents back to the host controller.  Returns None if
+     * the node is not a USB device (e.g., if it is an interface of a
+     * composite device, or not USB at all), or if the port to which it or
+     * any hub above it is connected cannot be determined from its unit
+     * address.
+     */
+    pub fn from_node(node: Node<'a>) -> Result<Option<Self>> {
+        if !is_usb_device(&node) {
+            return Ok(None);
+        }
+
+        let Some(port) = port_number(&node) else {
+            return Ok(None);
+        };
+        let mut ports = vec![port];
+        let mut n = node.clone();
+        let controller = loop {
+            let Some(p) = n.parent()? else {
+                return Ok(None);
+            };
+            if is_root_hub(&p) {
+                break p;
+            }
+            if !is_usb_device(&p) {
+                return Ok(None);
+            }
+            let Some(port) = port_number(&p) else {
+                return Ok(None);
+            };
+            ports.push(port);
+            n = p;
+        };
+        ports.reverse();
+
+        Ok(Some(UsbDevice { node, controller, ports }))
+    }
+
+    pub fn node(&self) -> &Node<'a> {
+        &self.node
+    }
+
+    /**
+     * The node of the host controller (e.g., xhci(4D)) that provides the
+     * root hub to which the device is ultimately connected.
+     */
+    pub fn controller(&self) -> &Node<'a> {
+        &self.controller
+    }
+
+    /**
+     * The hub ports through which the device is connected, starting with the
+     * port on the root hub and ending with the port to which the device
+     * itself is connected.
+     */
+    pub fn ports(&self) -> &[u32] {
+        &self.ports
+    }
+
+    /**
+     * A location string that identifies the port to which the device is
+     * connected, made up of the host controller driver and instance and the
+     * chain of hub ports; e.g., "xhci0:3.1" for a device on port 1 of a hub
+     * that is itself on port 3 of the root hub.  The location does not
+     * depend on the device, so identical devices in different ports can be
+     * told apart, and a device moved to another port is seen as such.
+     * Instance numbers are persistent, so the location is stable across
+     * reboots as long as the hardware is not reconfigured.
+     */
+    pub fn location(&self) -> Result<String> {
+        let ctlr =
+            match (self.controller.driver_name(), self.controller.instance()) {
+                (Some(d), Some(i)) => format!("{}{}", d, i),
+                _ => self.controller.devfs_path()?,
+            };
+        let ports =
+            self.ports.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        Ok(format!("{}:{}", ctlr, ports.join(".")))
+    }
+
+    /**
+     * The USB address assigned to the device on its bus.  Unlike the port
+     * location, this changes whenever the device is enumerated.
+     */
+    pub fn address(&self) -> Option<u32> {
+        self.node.prop_i32("assigned-address").and_then(|a| a.try_into().ok())
+    }
+
+    pub fn vendor_id(&self) -> Option<u16> {
+        self.node.prop_i32("usb-vendor-id").and_then(|v| v.try_into().ok())
+    }
+
+    pub fn product_id(&self) -> Option<u16> {
+        self.node.prop_i32("usb-product-id").and_then(|v| v.try_into().ok())
+    }
+
+    pub fn vendor_name(&self) -> Option<String> {
+        self.node.prop_str("usb-vendor-name").map(|s| s.trim().to_string())
+    }
+
+    pub fn product_name(&self) -> Option<String> {
+        self.node.prop_str("usb-product-name").map(|s| s.trim().to_string())
+    }
+
+    pub fn serial(&self) -> Option<String> {
+        self.node.prop_str("usb-serialno")
+    }
+
+    /**
+     * The speed at which the device is operating, as determined by the hub
+     * port to which it is connected.
+     */
+    pub fn speed(&self) -> Option<UsbSpeed> {
+        [
+            ("super-speed", UsbSpeed::Super),
+            ("high-speed", UsbSpeed::High),
+            ("full-speed", UsbSpeed::Full),
+            ("low-speed", UsbSpeed::Low),
+        ]
+        .into_iter()
+        .find(|(p, _)| self.node.prop_bool(p))
+        .map(|(_, s)| s)
+    }
+
+    /**
+     * The version of the USB specification with which the device complies
+     * (bcdUSB); e.g., 2.00.
+     */
+    pub fn usb_version(&self) -> Option<BcdVersion> {
+        self.node
+            .prop_i32("usb-release")
+            .and_then(|v| v.try_into().ok())
+            .map(BcdVersion)
+            .or_else(|| Some(self.device_descriptor()?.usb_version))
+    }
+
+    /**
+     * The release number of the device (bcdDevice), as assigned by the
+     * manufacturer.
+     */
+    pub fn device_version(&self) -> Option<BcdVersion> {
+        self.node
+            .prop_i32("usb-revision-id")
+            .and_then(|v| v.try_into().ok())
+            .map(BcdVersion)
+            .or_else(|| Some(self.device_descriptor()?.device_version))
+    }
+
+    /**
+     * The device descriptor, if usba has published it.
+     */
+    pub fn device_descriptor(&self) -> Option<UsbDeviceDescriptor> {
+        UsbDeviceDescriptor::parse(self.node.prop_bytes("usb-dev-descriptor")?)
+    }
+
+    /**
+     * The configuration descriptors of the device, with their interface
+     * descriptors, if usba has published them.
+     */
+    pub fn config_descriptors(&self) -> Vec<UsbConfigDescriptor> {
+        self.node
+            .prop_bytes("usb-raw-cfg-descriptors")
+            .map(|b| parse_cfg_descriptors(b, self.speed()))
+            .unwrap_or_default()
+    }
+
+    /**
+     * Returns true if the device is a hub, to which other devices may be
+     * connected.
+     */
+    pub fn is_hub(&self) -> bool {
+        self.node.driver_name().as_deref() == Some("hubd")
+    }
+
+    /**
+     * Returns true if the device is a composite device, for which usb_mid(4D)
+     * has created a separate child node for each interface.
+     */
+    pub fn is_composite(&self) -> bool {
+        self.node.driver_name().as_deref() == Some("usb_mid")
+    }
+
+    /**
+     * Collect the interfaces of a composite device, each of which may have a
+     * different driver attached.  Interfaces grouped by an interface
+     * association, under a usb_ia(4D) node, are included as if they were
+     * direct children of the device.  A device that is not composite has a
+     * single driver attached to the device as a whole, and no interfaces are
+     * returned.
+     */
+    pub fn interfaces(&self) -> Vec<UsbInterface<'a>> {
+        let mut out = Vec::new();
+        if self.is_composite() {
+            collect_interfaces(&self.node, &mut out);
+        }
+        out.sort_by_key(|i| i.number());
+        out
+    }
+}
+
+fn collect_interfaces<'a>(n: &Node<'a>, out: &mut Vec<UsbInterface<'a>>) {
+    for c in n.children() {
+        if c.prop_i32("interface-count").is_some() {
+            /*
+             * This is an interface association, whose children are the
+             * interfaces it groups.
+             */
+            collect_interfaces(&c, out);
+        } else if c.prop_i32("interface").is_some() {
+            out.push(UsbInterface { node: c });
+        }
+    }
+}
+
+/**
+ * One interface of a composite USB device.
+ */
+#[derive(Clone)]
+pub struct UsbInterface<'a> {
+    node: Node<'a>,
+}
+
+impl<'a> UsbInterface<'a> {
+    pub fn node(&self) -> &Node<'a> {
+        &self.node
+    }
+
+    /**
+     * The interface number, from the interface descriptor.
+     */
+    pub fn number(&self) -> Option<u32> {
+        self.node.prop_i32("interface").and_then(|i| i.try_into().ok())
+    }
+
+    /**
+     * The driver attached to this interface, if any; e.g., "hid" or
+     * "usbsacm".
+     */
+    pub fn driver_name(&self) -> Option<String> {
+        self.node.driver_name()
+    }
+
+    /*
+     * usba includes the class, subclass, and protocol of the interface, in
+     * hexadecimal, in the most specific of its "usbif,class" compatible
+     * names; e.g., "usbif,class3.1.2".
+     */
+    fn class_triple(&self) -> Vec<u8> {
+        let Some(names) =
+            lookup_strings(DDI_DEV_T_ANY, self.node.node, "compatible")
+        else {
+            return Vec::new();
+        };
+
+        names
+            .iter()
+            .filter_map(|n| n.to_str().ok()?.strip_prefix("usbif,class"))
+            .map(|c| {
+                c.split('.')
+                    .map_while(|v| u8::from_str_radix(v, 16).ok())
+                    .collect::<Vec<_>>()
+            })
+            .max_by_key(|c| c.len())
+            .unwrap_or_default()
+    }
+
+    /**
+     * The interface class code; e.g., 0x3 for a human interface device.
+     */
+    pub fn class(&self) -> Option<u8> {
+        self.class_triple().first().copied()
+    }
+
+    pub fn subclass(&self) -> Option<u8> {
+        self.class_triple().get(1).copied()
+    }
+
+    pub fn protocol(&self) -> Option<u8> {
+        self.class_triple().get(2).copied()
+    }
+}
+
+impl std::fmt::Debug for UsbInterface<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UsbInterface")
+            .field("number", &self.number())
+            .field("class", &self.class())
+            .field("subclass", &self.subclass())
+            .field("protocol", &self.protocol())
+            .field("driver_name", &self.driver_name())
+            .finish()
+    }
+}
+
+impl std::fmt::Debug for UsbDevice<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UsbDevice")
+            .field("location", &self.location().ok())
+            .field("vendor_id", &self.vendor_id())
+            .field("product_id", &self.product_id())
+            .field("serial", &self.serial())
+            .field("speed", &self.speed())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /*
+     * The device descriptor of a USB 2.0 hub.
+     */
+    const DEV: [u8; 18] = [
+        0x12, 0x01, 0x10, 0x02, 0x09, 0x00, 0x01, 0x40, 0x24, 0x04, 0x12, 0x25,
+        0xb3, 0x0b, 0x00, 0x00, 0x00, 0x01,
+    ];
+
+    /*
+     * A configuration with two interfaces, the second of which has an
+     * alternate setting, along with endpoint descriptors that are skipped.
+     */
+    #[rustfmt::skip]
+    const CFG: [u8; 50] = [
+        /* configuration */
+        0x09, 0x02, 0x32, 0x00, 0x02, 0x01, 0x00, 0xe0, 0x32,
+        /* interface 0 */
+        0x09, 0x04, 0x00, 0x00, 0x01, 0x03, 0x01, 0x02, 0x00,
+        /* endpoint */
+        0x07, 0x05, 0x81, 0x03, 0x08, 0x00, 0x0a,
+        /* interface 1 */
+        0x09, 0x04, 0x01, 0x00, 0x00, 0x0e, 0x02, 0x00, 0x00,
+        /* interface 1, alternate setting 1 */
+        0x09, 0x04, 0x01, 0x01, 0x01, 0x0e, 0x02, 0x00, 0x00,
+        /* endpoint */
+        0x07, 0x05, 0x82, 0x05, 0x00, 0x02, 0x01,
+    ];
+
+    #[test]
+    fn device() {
+        let d = UsbDeviceDescriptor::parse(&DEV).unwrap();
+        assert_eq!(d.usb_version, BcdVersion(0x0210));
+        assert_eq!(d.usb_version.to_string(), "2.10");
+        assert_eq!((d.class, d.subclass, d.protocol), (9, 0, 1));
+        assert_eq!(d.max_packet_size0, 64);
+        assert_eq!(d.vendor_id, 0x0424);
+        assert_eq!(d.product_id, 0x2512);
+        assert_eq!(d.device_version.to_string(), "b.b3");
+        assert_eq!(d.num_configurations, 1);
+    }
+
+    #[test]
+    fn device_malformed() {
+        assert_eq!(UsbDeviceDescriptor::parse(&[]), None);
+        assert_eq!(UsbDeviceDescriptor::parse(&DEV[..17]), None);
+
+        let mut wrong_type = DEV;
+        wrong_type[1] = USB_DESCR_TYPE_CFG;
+        assert_eq!(UsbDeviceDescriptor::parse(&wrong_type), None);
+    }
+
+    #[test]
+    fn config() {
+        let cfgs = parse_cfg_descriptors(&CFG, Some(UsbSpeed::High));
+        assert_eq!(cfgs.len(), 1);
+        let c = &cfgs[0];
+        assert_eq!(c.value, 1);
+        assert!(c.self_powered());
+        assert!(c.remote_wakeup());
+        assert_eq!(c.max_power_ma, 100);
+
+        let ifs: Vec<_> =
+            c.interfaces.iter().map(|i| (i.number, i.alternate)).collect();
+        assert_eq!(ifs, [(0, 0), (1, 0), (1, 1)]);
+        assert_eq!(
+            c.interfaces[0],
+            UsbInterfaceDescriptor {
+                number: 0,
+                alternate: 0,
+                num_endpoints: 1,
+                class: 3,
+                subclass: 1,
+                protocol: 2,
+            }
+        );
+
+        /*
+         * Power is in units of 8mA for super-speed devices.
+         */
+        let cfgs = parse_cfg_descriptors(&CFG, Some(UsbSpeed::Super));
+        assert_eq!(cfgs[0].max_power_ma, 400);
+
+        /*
+         * Several configurations may be concatenated.
+         */
+        let two = [CFG, CFG].concat();
+        assert_eq!(parse_cfg_descriptors(&two, None).len(), 2);
+    }
+
+    #[test]
+    fn config_malformed() {
+        assert!(parse_cfg_descriptors(&[], None).is_empty());
+        assert!(parse_cfg_descriptors(&[0x09], None).is_empty());
+
+        /*
+         * A descriptor whose length runs past the end of the buffer ends the
+         * parse, keeping what came before.
+         */
+        let cfgs = parse_cfg_descriptors(&CFG[..40], None);
+        assert_eq!(cfgs.len(), 1);
+        assert_eq!(cfgs[0].interfaces.len(), 2);
+
+        /*
+         * A zero length would never advance, and must end the parse.
+         */
+        let mut zero = CFG;
+        zero[9] = 0;
+        assert_eq!(parse_cfg_descriptors(&zero, None)[0].interfaces.len(), 0);
+
+        /*
+         * Interfaces that appear before any configuration, and descriptors
+         * too short for their type, are ignored.
+         */
+        let orphan =
+            [&CFG[9..18], &[0x05, 0x02, 0, 0, 0][..], &CFG[..]].concat();
+        let cfgs = parse_cfg_descriptors(&orphan, None);
+        assert_eq!(cfgs.len(), 1);
+        assert_eq!(cfgs[0].interfaces.len(), 3);
+    }
+}
@@ -0,0 +1,356 @@
+/*
+ * Copyright 2024 Oxide Computer Company
+ */
+
+/*
+ * Walks of the device tree in orders other than the depth-first order used by
+ * NodeWalk, or that provide more context about each node.
+ */
+
+use crate::sys::*;
+use crate::{DevInfo, Minor, Node, NodeWalk};
+use anyhow::Result;
+use std::collections::VecDeque;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum WalkOrder {
+    /**
+     * Visit each node, then its children, before moving on to its next
+     * sibling; i.e., the order used by NodeWalk.
+     */
+    #[default]
+    DepthFirst,
+    /**
+     * Visit every node at one depth before any node at the next depth.  This
+     * is also the order used by di_walk_node(3DEVINFO) with DI_WALK_SIBFIRST,
+     * which appends the children of each node it visits to the end of a
+     * queue.
+     */
+    BreadthFirst,
+}
+
+pub struct OrderedNodeWalk<'w> {
+    parent: &'w DevInfo,
+    order: WalkOrder,
+    sorted: bool,
+    pending: VecDeque<*mut di_node_t>,
+}
+
+fn children(node: *mut di_node_t) -> Vec<*mut di_node_t> {
+    let mut out = Vec::new();
+    let mut child = unsafe { di_child_node(node) };
+    while child != DI_NODE_NIL {
+        out.push(child);
+        child = unsafe { di_sibling_node(child) };
+    }
+    out
+}
+
+/*
+ * Return the children of a node, optionally sorted by node name and then unit
+ * address, so that the order does not depend on the order in which devices
+ * happened to attach.
+ */
+fn children_ordered(
+    di: &DevInfo,
+    node: *mut di_node_t,
+    sorted: bool,
+) -> Vec<*mut di_node_t> {
+    let mut out = children(node);
+    if sorted {
+        out.sort_by_cached_key(|&node| {
+            let n = Node { parent: di, node };
+            (n.node_name(), n.unit_address())
+        });
+    }
+    out
+}
+
+/*
+ * Take the next node to visit from the front of the queue, and queue its
+ * children where the walk order requires.  This is generic over the type of
+ * node so that the order can be checked without a snapshot.
+ */
+fn walk_step<T>(
+    order: WalkOrder,
+    pending: &mut VecDeque<T>,
+    children: impl FnOnce(&T) -> Vec<T>,
+) -> Option<T> {
+    let node = pending.pop_front()?;
+    let children = children(&node);
+
+    match order {
+        WalkOrder::DepthFirst => {
+            /*
+             * The children of this node come before any of the nodes we have
+             * already queued; i.e., its siblings and those of its ancestors.
+             */
+            for child in children.into_iter().rev() {
+                pending.push_front(child);
+            }
+        }
+        WalkOrder::BreadthFirst => {
+            pending.extend(children);
+        }
+    }
+
+    Some(node)
+}
+
+impl<'a> Iterator for OrderedNodeWalk<'a> {
+    type Item = Result<Node<'a>>;
+
+    fn next(&mut self) -> Option<Result<Node<'a>>> {
+        let (di, sorted) = (self.parent, self.sorted);
+        let node = walk_step(self.order, &mut self.pending, |&node| {
+            children_ordered(di, node, sorted)
+        })?;
+
+        Some(Ok(Node { parent: self.parent, node }))
+    }
+}
+
+impl<'a> OrderedNodeWalk<'a> {
+    /**
+     * Visit the children of each node sorted by node name and unit address,
+     * rather than in the order in which they appear in the snapshot, so that
+     * the order of the walk is the same from one boot to the next.
+     */
+    pub fn sorted(mut self) -> Self {
+        self.sorted = true;
+        self
+    }
+}
+
+/**
+ * The action to take after a node is visited by DevInfo::walk_with(), with
+ * the same meanings as the return values of the callback passed to
+ * di_walk_node(3DEVINFO).
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WalkAction {
+    /**
+     * Continue the walk.
+     */
+    Continue,
+    /**
+     * Continue the walk, but skip the children of this node.
+     */
+    PruneChild,
+    /**
+     * Continue the walk, but skip the siblings of this node that have not
+     * yet been visited.  The children of this node are still visited.
+     */
+    PruneSib,
+    /**
+     * End the walk.
+     */
+    Terminate,
+}
+
+/**
+ * A node visited by an EntryWalk, along with its position in the tree.
+ */
+#[derive(Clone)]
+pub struct WalkEntry<'a> {
+    pub node: Node<'a>,
+    /**
+     * The depth of the node, as would be returned by Node::depth().
+     */
+    pub depth: u32,
+    /**
+     * The parent of the node, or None for the root of the snapshot.
+     */
+    pub parent: Option<Node<'a>>,
+}
+
+/**
+ * A depth-first walk, in the same order as NodeWalk, that yields the depth
+ * and parent of each node along with the node itself.  These are tracked as
+ * the walk proceeds, rather than by walking back up to the root for each
+ * node.
+ */
+pub struct EntryWalk<'w> {
+    parent: &'w DevInfo,
+    pending: Vec<(*mut di_node_t, u32, *mut di_node_t)>,
+    last: Option<(*mut di_node_t, u32)>,
+    skip_children: bool,
+    sorted: bool,
+}
+
+impl<'a> EntryWalk<'a> {
+    /**
+     * Do not visit the children of the most recent node.
+     */
+    pub fn skip_children(&mut self) {
+        self.skip_children = true;
+    }
+
+    /**
+     * Visit the children of each node sorted by node name and unit address;
+     * see OrderedNodeWalk::sorted().
+     */
+    pub fn sorted(mut self) -> Self {
+        self.sorted = true;
+        self
+    }
+}
+
+impl<'a> Iterator for EntryWalk<'a> {
+    type Item = Result<WalkEntry<'a>>;
+
+    fn next(&mut self) -> Option<Result<WalkEntry<'a>>> {
+        /*
+         * The children of the most recent node are not queued until now, in
+         * case the consumer asked for them to be skipped.
+         */
+        if let Some((last, depth)) = self.last.take() {
+            if !std::mem::take(&mut self.skip_children) {
+                let children = children_ordered(self.parent, last, self.sorted);
+                for child in children.into_iter().rev() {
+                    self.pending.push((child, depth + 1, last));
+                }
+            }
+        }
+
+        let (node, depth, parent) = self.pending.pop()?;
+        self.last = Some((node, depth));
+
+        Some(Ok(WalkEntry {
+            node: Node { parent: self.parent, node },
+            depth,
+            parent: if parent == DI_NODE_NIL {
+                None
+            } else {
+                Some(Node { parent: self.parent, node: parent })
+            },
+        }))
+    }
+}
+
+/**
+ * A depth-first walk that yields each node along with all of its minor nodes.
+ */
+pub struct NodeMinorsWalk<'w> {
+    nodes: NodeWalk<'w>,
+}
+
+impl<'a> NodeMinorsWalk<'a> {
+    /**
+     * Do not visit the children of the most recent node.
+     */
+    pub fn skip_children(&mut self) {
+        self.nodes.skip_children();
+    }
+}
+
+impl<'a> Iterator for NodeMinorsWalk<'a> {
+    type Item = Result<(Node<'a>, Vec<Minor<'a>>)>;
+
+    fn next(&mut self) -> Option<Result<(Node<'a>, Vec<Minor<'a>>)>> {
+        let n = match self.nodes.next()? {
+            Ok(n) => n,
+            Err(e) => return Some(Err(e)),
+        };
+
+        match n.minors().collect::<Result<Vec<_>>>() {
+            Ok(minors) => Some(Ok((n, minors))),
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+impl DevInfo {
+    /**
+     * Walk every node in the snapshot in the given order.
+     */
+    pub fn walk_node_ordered(&self, order: WalkOrder) -> OrderedNodeWalk<'_> {
+        OrderedNodeWalk {
+            parent: self,
+            order,
+            sorted: false,
+            pending: VecDeque::from([self.root]),
+        }
+    }
+
+    /**
+     * Walk every node in the snapshot, yielding each node along with its
+     * minor nodes.  The snapshot must include minor nodes for the list to be
+     * populated.
+     */
+    pub fn walk_node_minors(&self) -> NodeMinorsWalk<'_> {
+        NodeMinorsWalk { nodes: self.walk_node() }
+    }
+
+    /**
+     * Walk every node in the snapshot in depth-first order, calling the
+     * provided function for each node.  The function decides whether and how
+     * the walk proceeds.
+     */
+    pub fn walk_with<F>(&self, mut func: F) -> Result<()>
+    where
+        F: FnMut(&Node) -> WalkAction,
+    {
+        let mut w = self.walk_node();
+        while let Some(n) = w.next().transpose()? {
+            match func(&n) {
+                WalkAction::Continue => (),
+                WalkAction::PruneChild => w.skip_children(),
+                WalkAction::PruneSib => w.skip_siblings(),
+                WalkAction::Terminate => break,
+            }
+        }
+
+        Ok(())
+    }
+
+    /**
+     * Walk every node in the snapshot, yielding the depth and parent of each
+     * node along with the node itself.
+     */
+    pub fn walk_entries(&self) -> EntryWalk<'_> {
+        EntryWalk {
+            parent: self,
+            pending: vec![(self.root, 1, DI_NODE_NIL)],
+            last: None,
+            skip_children: false,
+            sorted: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /*
+     *            0
+     *          /   \
+     *         1     2
+     *        / \     \
+     *       3   4     5
+     *       |
+     *       6
+     */
+    fn children(n: &u32) -> Vec<u32> {
+        match n {
+            0 => vec![1, 2],
+            1 => vec![3, 4],
+            2 => vec![5],
+            3 => vec![6],
+            _ => vec![],
+        }
+    }
+
+    fn walk(order: WalkOrder) -> Vec<u32> {
+        let mut pending = VecDeque::from([0]);
+        std::iter::from_fn(|| walk_step(order, &mut pending, children))
+            .collect()
+    }
+
+    #[test]
+    fn order() {
+        assert_eq!(walk(WalkOrder::DepthFirst), [0, 1, 3, 6, 4, 2, 5]);
+        assert_eq!(walk(WalkOrder::BreadthFirst), [0, 1, 2, 3, 4, 5, 6]);
+    }
+}
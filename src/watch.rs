@@ -0,0 +1,214 @@
+/*
+ * Copyright 2024 Oxide Computer Company
+ */
+
+/*
+ * Change notification by periodically taking a new snapshot and comparing it
+ * with the previous one.  This is more expensive than subscribing to device
+ * events, but it does not require any particular privileges and it catches
+ * changes for which no event is posted.
+ */
+
+use crate::{
+    DevInfo, DevInfoBuilder, DevLink, DevLinks, OwnedDevTree, SnapshotDiff,
+    SnapshotFlags,
+};
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+const WATCH_INTERVAL_DEFAULT: Duration = Duration::from_secs(5);
+
+/**
+ * The changes observed by a Watcher between one snapshot and the next.
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WatchUpdate {
+    /**
+     * Nodes, minors, and properties that were added, removed, or changed.
+     */
+    pub diff: SnapshotDiff,
+    /**
+     * Links in /dev that were created or changed, if the watcher is
+     * following links.
+     */
+    pub links_added: Vec<DevLink>,
+    /**
+     * Links in /dev that were removed or changed, if the watcher is
+     * following links.
+     */
+    pub links_removed: Vec<DevLink>,
+}
+
+impl WatchUpdate {
+    pub fn is_empty(&self) -> bool {
+        self.diff.is_empty()
+            && self.links_added.is_empty()
+            && self.links_removed.is_empty()
+    }
+}
+
+/**
+ * Compose the options for a Watcher.  By default, a snapshot of the whole
+ * tree with SnapshotFlags::CPYALL is taken every five seconds, and links are
+ * not followed.
+ */
+#[derive(Clone, Debug)]
+pub struct WatcherBuilder {
+    snapshot: DevInfoBuilder,
+    interval: Duration,
+    links: bool,
+}
+
+impl WatcherBuilder {
+    /**
+     * How often to take a new snapshot.
+     */
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /**
+     * The options for each snapshot; e.g., to watch only a subtree.
+     */
+    pub fn snapshot(mut self, snapshot: DevInfoBuilder) -> Self {
+        self.snapshot = snapshot;
+        self
+    }
+
+    /**
+     * Replace the set of flags used for each snapshot.  Minor and property
+     * changes are only reported if the snapshot includes them.
+     */
+    pub fn flags(mut self, flags: SnapshotFlags) -> Self {
+        self.snapshot = self.snapshot.flags(flags);
+        self
+    }
+
+    /**
+     * Also report links in /dev that are created or removed.
+     */
+    pub fn links(mut self, links: bool) -> Self {
+        self.links = links;
+        self
+    }
+
+    /**
+     * Take the initial snapshot, against which the first update is computed.
+     */
+    pub fn build(self) -> Result<Watcher> {
+        let tree = take_tree(&self.snapshot)?;
+        let links = if self.links { Some(take_links()?) } else { None };
+
+        Ok(Watcher {
+            snapshot: self.snapshot,
+            interval: self.interval,
+            tree,
+            links,
+            last: Instant::now(),
+        })
+    }
+}
+
+fn take_tree(snapshot: &DevInfoBuilder) -> Result<OwnedDevTree> {
+    snapshot.clone().build()?.to_owned_tree()
+}
+
+fn take_links() -> Result<BTreeMap<PathBuf, DevLink>> {
+    Ok(DevLinks::new(false)?
+        .all_links()?
+        .into_iter()
+        .map(|l| (l.path().to_path_buf(), l))
+        .collect())
+}
+
+/**
+ * Watch for changes to the device tree by taking a new snapshot at a regular
+ * interval and comparing it with the previous one.
+ */
+pub struct Watcher {
+    snapshot: DevInfoBuilder,
+    interval: Duration,
+    tree: OwnedDevTree,
+    links: Option<BTreeMap<PathBuf, DevLink>>,
+    last: Instant,
+}
+
+impl Watcher {
+    pub fn builder() -> WatcherBuilder {
+        WatcherBuilder {
+            snapshot: DevInfo::builder(),
+            interval: WATCH_INTERVAL_DEFAULT,
+            links: false,
+        }
+    }
+
+    pub fn new(interval: Duration) -> Result<Self> {
+        Self::builder().interval(interval).build()
+    }
+
+    /**
+     * Take a new snapshot now, and report any changes since the previous
+     * one.  The result may be empty.
+     */
+    pub fn poll(&mut self) -> Result<WatchUpdate> {
+        let tree = take_tree(&self.snapshot)?;
+        let mut update =
+            WatchUpdate { diff: self.tree.diff(&tree), ..Default::default() };
+        self.tree = tree;
+
+        if let Some(old) = self.links.as_mut() {
+            let new = take_links()?;
+            for (path, l) in old.iter() {
+                if new.get(path) != Some(l) {
+                    update.links_removed.push(l.clone());
+                }
+            }
+            for (path, l) in new.iter() {
+                if old.get(path) != Some(l) {
+                    update.links_added.push(l.clone());
+                }
+            }
+            *old = new;
+        }
+
+        self.last = Instant::now();
+        Ok(update)
+    }
+
+    /**
+     * Wait until a snapshot differs from the previous one, taking a new
+     * snapshot at the configured interval, and report the changes.
+     */
+    pub fn wait(&mut self) -> Result<WatchUpdate> {
+        loop {
+            std::thread::sleep(
+                self.interval.saturating_sub(self.last.elapsed()),
+            );
+
+            let update = self.poll()?;
+            if !update.is_empty() {
+                return Ok(update);
+            }
+        }
+    }
+
+    /**
+     * The most recent snapshot.
+     */
+    pub fn tree(&self) -> &OwnedDevTree {
+        &self.tree
+    }
+}
+
+impl std::fmt::Debug for Watcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Watcher")
+            .field("snapshot", &self.snapshot)
+            .field("interval", &self.interval)
+            .field("links", &self.links.is_some())
+            .finish()
+    }
+}